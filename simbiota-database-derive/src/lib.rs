@@ -0,0 +1,140 @@
+//! Derive macros for `simbiota-database`.
+//!
+//! `#[derive(DatabaseRecord)]` implements `simbiota_database::record::DatabaseRecord` for a
+//! struct with named fields of byte arrays and integers, generating the serialization and the
+//! size validation of the entries. Use it through the `derive` feature of `simbiota-database`.
+//!
+//! Struct attributes:
+//! - `#[record(format = 0x0003, entry_type = 0)]`: format and entry type of the objects, both
+//!   required
+//! - `#[record(name = "MyFormat")]`: name of the format, the struct name by default
+//!
+//! Field attributes:
+//! - `#[record(little_endian)]`: store an integer field in little-endian byte order instead of
+//!   big-endian
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, LitInt, LitStr};
+
+#[proc_macro_derive(DatabaseRecord, attributes(record))]
+pub fn derive_database_record(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let ident = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "DatabaseRecord cannot be derived for generic structs",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    ident,
+                    "DatabaseRecord requires a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                ident,
+                "DatabaseRecord can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut format: Option<u16> = None;
+    let mut entry_type: Option<u16> = None;
+    let mut name = LitStr::new(&ident.to_string(), ident.span());
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("record")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("format") {
+                format = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+            } else if meta.path.is_ident("entry_type") {
+                entry_type = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+            } else if meta.path.is_ident("name") {
+                name = meta.value()?.parse()?;
+            } else {
+                return Err(meta.error("unknown record attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    let missing = |attribute: &str| {
+        Error::new(
+            Span::call_site(),
+            format!("missing #[record({} = ...)] attribute", attribute),
+        )
+    };
+    let format = format.ok_or_else(|| missing("format"))?;
+    let entry_type = entry_type.ok_or_else(|| missing("entry_type"))?;
+
+    let mut field_names = Vec::new();
+    let mut field_types = Vec::new();
+    let mut big_endian = Vec::new();
+    for field in fields {
+        let mut little_endian = false;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("record")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("little_endian") {
+                    little_endian = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown record field attribute"))
+                }
+            })?;
+        }
+        field_names.push(field.ident.clone().unwrap());
+        field_types.push(&field.ty);
+        big_endian.push(!little_endian);
+    }
+
+    let field_type = quote!(::simbiota_database::record::RecordField);
+    // The generated locals share a scope with the field bindings, so they are prefixed to not
+    // collide with field names
+    Ok(quote! {
+        impl ::simbiota_database::record::DatabaseRecord for #ident {
+            const NAME: &'static str = #name;
+            const FORMAT: u16 = #format;
+            const ENTRY_TYPE: u16 = #entry_type;
+            const ENTRY_SIZE: u16 = {
+                let __simbiota_size = 0 #(+ <#field_types as #field_type>::SIZE)*;
+                assert!(__simbiota_size <= u16::MAX as usize, "record too large");
+                __simbiota_size as u16
+            };
+
+            fn to_entry(&self) -> ::std::vec::Vec<u8> {
+                let mut __simbiota_entry =
+                    ::std::vec::Vec::with_capacity(Self::ENTRY_SIZE as usize);
+                #(#field_type::write(&self.#field_names, &mut __simbiota_entry, #big_endian);)*
+                __simbiota_entry
+            }
+
+            fn from_entry(__simbiota_entry: &[u8]) -> ::std::option::Option<Self> {
+                if __simbiota_entry.len() != Self::ENTRY_SIZE as usize {
+                    return ::std::option::Option::None;
+                }
+                let mut __simbiota_offset = 0;
+                #(
+                    let __simbiota_size = <#field_types as #field_type>::SIZE;
+                    let #field_names = <#field_types as #field_type>::read(
+                        &__simbiota_entry[__simbiota_offset..__simbiota_offset + __simbiota_size],
+                        #big_endian,
+                    )?;
+                    __simbiota_offset += __simbiota_size;
+                )*
+                let _ = __simbiota_offset;
+                ::std::option::Option::Some(Self { #(#field_names),* })
+            }
+        }
+    })
+}
@@ -0,0 +1,108 @@
+use simbiota_database::formats::colored_tlsh::FamilyLabel;
+use simbiota_database::formats::colored_tlsh_with_distance::ColoredTLSHWithDistanceObject;
+use simbiota_database::hash::Sha256;
+use simbiota_database::record::{DatabaseRecord, RecordObject};
+use simbiota_database::{Database, FormatError, ObjectFormat, ObjectImpl};
+
+#[derive(DatabaseRecord, Debug, PartialEq)]
+#[record(format = 0x0003, entry_type = 0)]
+struct DistanceRecord {
+    tlsh: [u8; 36],
+    sha256: [u8; 32],
+    distance: u8,
+}
+
+#[derive(DatabaseRecord, Debug, PartialEq)]
+#[record(format = 0x7f10, entry_type = 2, name = "Counters")]
+struct CounterRecord {
+    id: u32,
+    #[record(little_endian)]
+    count: u64,
+    delta: i16,
+}
+
+/// Field names that match the locals of the generated code
+#[derive(DatabaseRecord, Debug, PartialEq)]
+#[record(format = 0x7f11, entry_type = 0)]
+struct ShadowingRecord {
+    offset: u8,
+    size: u16,
+    entry: u32,
+    x: u8,
+}
+
+#[test]
+fn test_record_layout() {
+    assert_eq!(DistanceRecord::ENTRY_SIZE, 36 + 32 + 1);
+    assert_eq!(DistanceRecord::NAME, "DistanceRecord");
+    assert_eq!(CounterRecord::ENTRY_SIZE, 4 + 8 + 2);
+    assert_eq!(CounterRecord::NAME, "Counters");
+    assert_eq!(
+        <RecordObject<CounterRecord> as ObjectImpl>::NAME,
+        "Counters"
+    );
+
+    let record = CounterRecord {
+        id: 1,
+        count: 2,
+        delta: -1,
+    };
+    let entry = record.to_entry();
+    assert_eq!(entry, vec![0, 0, 0, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff]);
+    assert_eq!(CounterRecord::from_entry(&entry), Some(record));
+    assert_eq!(CounterRecord::from_entry(&entry[1..]), None);
+
+    let record = ShadowingRecord {
+        offset: 1,
+        size: 2,
+        entry: 3,
+        x: 4,
+    };
+    assert_eq!(ShadowingRecord::ENTRY_SIZE, 1 + 2 + 4 + 1);
+    let entry = record.to_entry();
+    assert_eq!(entry, vec![1, 0, 2, 0, 0, 0, 3, 4]);
+    assert_eq!(ShadowingRecord::from_entry(&entry), Some(record));
+}
+
+#[test]
+fn test_record_object() {
+    let mut object = RecordObject::new();
+    object.add_record(DistanceRecord {
+        tlsh: [1; 36],
+        sha256: [2; 32],
+        distance: 40,
+    });
+
+    // The derived layout is compatible with the hand-written format
+    let mut database = Database::new(1);
+    database.add_object(1, object.to_object());
+    let database = Database::from_bytes(database.as_bytes().as_slice()).unwrap();
+    let object = database.get_object(1).unwrap();
+    let manual = ColoredTLSHWithDistanceObject::try_from_object(object).unwrap();
+    assert_eq!(manual.get_entries()[0].distance, 40);
+    assert_eq!(manual.get_entries()[0].sha256_hash, Sha256::from([2; 32]));
+
+    let records = RecordObject::<DistanceRecord>::try_from(object).unwrap();
+    assert_eq!(records.get_records()[0].tlsh, [1; 36]);
+
+    // Format, entry type and entry size are validated
+    assert_eq!(
+        RecordObject::<CounterRecord>::try_from_object(object).err(),
+        Some(FormatError::WrongFormat {
+            expected: 0x7f10,
+            found: 0x0003
+        })
+    );
+    let mut labelled = ColoredTLSHWithDistanceObject::empty();
+    let label = FamilyLabel {
+        string_table_id: 2,
+        index: 0,
+    };
+    labelled.add_labelled_entry([1; 36], [2; 32], 40, label);
+    let labelled = labelled.to_object();
+    assert_eq!(
+        RecordObject::<DistanceRecord>::try_from_object(&labelled).err(),
+        Some(FormatError::UnsupportedEntryType(1))
+    );
+    assert!(RecordObject::<DistanceRecord>::from_object(labelled).is_none());
+}
@@ -0,0 +1,267 @@
+//! Asynchronous lazy database for `tokio` applications.
+//!
+//! [`AsyncLazyDatabase`] offers the same object catalog as [`LazyLoadedDatabase`], but never
+//! blocks the runtime threads. The header and the object map are read with `tokio`'s file API,
+//! objects are read and decompressed on the blocking thread pool.
+//!
+//! [`LazyLoadedDatabase`]: crate::LazyLoadedDatabase
+//!
+//! # Example: Read an object from an async context
+//! ```rust no_run
+//! use simbiota_database::async_database::AsyncLazyDatabase;
+//! use simbiota_database::formats::simple_tlsh::SimpleTLSHObject;
+//! use simbiota_database::ObjectImpl;
+//!
+//! async fn load_hashes() -> Vec<String> {
+//!     let database = AsyncLazyDatabase::open("database_file.sdb").await.unwrap();
+//!     let object = database.get_object(0x0001).await.expect("object not found");
+//!     let tlsh_list = SimpleTLSHObject::from_object(object).expect("failed to parse object");
+//!     tlsh_list.get_hashes()
+//! }
+//! ```
+
+use crate::database::{
+    parse_v1_extra_data, read_object, read_object_metadata, Object, ObjectFormat,
+};
+use crate::header::Header;
+use crate::object::ObjectDecodeError;
+use crate::object_map::ObjectMap;
+use crate::raw_database_file::DatabaseParseError;
+use crate::raw_database_file::DatabaseParseError::{
+    FileOpenFailed, IOError, InvalidHeader, InvalidObjectMap,
+};
+use crate::source::{DatabaseInfo, GetObjectError, ObjectMetadata, SourceError};
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+
+/// Asynchronous version of [`LazyLoadedDatabase`](crate::LazyLoadedDatabase).
+///
+/// Only the header and the object map are kept in memory, objects are read from the file
+/// on each request.
+pub struct AsyncLazyDatabase {
+    file: Arc<File>,
+    header: Header,
+    mapping: ObjectMap,
+    last_updated: u64,
+    database_version: u64,
+}
+
+impl AsyncLazyDatabase {
+    /// Open the database at the specified path. The header and object map are loaded and kept
+    /// in memory, but no objects are loaded.
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, DatabaseParseError> {
+        let mut file = tokio::fs::File::open(path).await.map_err(FileOpenFailed)?;
+        let file_len = file.metadata().await.map_err(IOError)?.len();
+
+        // Read minimal header
+        let mut minimal_header_buf = [0u8; 0x20];
+        file.read_exact(&mut minimal_header_buf)
+            .await
+            .map_err(IOError)?;
+        let length = u32::from_be_bytes((&minimal_header_buf[16..20]).try_into().unwrap());
+        if (length as usize) < minimal_header_buf.len() || length as u64 > file_len {
+            return Err(InvalidHeader(crate::header::HeaderDecodeError::TooShort));
+        }
+        let mut header_data = minimal_header_buf.to_vec();
+        header_data.resize(length as usize, 0);
+        file.read_exact(&mut header_data[minimal_header_buf.len()..])
+            .await
+            .map_err(IOError)?;
+
+        let header = Header::try_from(header_data.as_slice()).map_err(InvalidHeader)?;
+        let (last_updated, database_version) = parse_v1_extra_data(&header.extra_data)?;
+
+        // The object map follows the header directly
+        let mapping_size = ObjectMap::checked_length(
+            header.version,
+            header.number_of_objects,
+            header.header_len,
+            file_len,
+        )
+        .map_err(InvalidObjectMap)?;
+        let mut mapping_data = vec![0u8; mapping_size];
+        file.read_exact(mapping_data.as_mut_slice())
+            .await
+            .map_err(IOError)?;
+        let mapping = ObjectMap::try_from_version(
+            mapping_data.as_slice(),
+            header.number_of_objects,
+            header.version,
+        )
+        .map_err(InvalidObjectMap)?;
+
+        Ok(Self {
+            file: Arc::new(file.into_std().await),
+            header,
+            mapping,
+            last_updated,
+            database_version,
+        })
+    }
+
+    /// IDs of all the objects in the database.
+    pub fn object_ids(&self) -> Vec<u64> {
+        self.mapping.mappings().iter().map(|m| m.id).collect()
+    }
+
+    /// Check if the database contains a specified object.
+    pub fn has_object(&self, id: u64) -> bool {
+        self.mapping.contains(id)
+    }
+
+    /// General information about the database.
+    pub fn info(&self) -> DatabaseInfo {
+        DatabaseInfo {
+            file_version: self.header.version,
+            database_version: self.database_version,
+            last_updated: self.last_updated,
+            number_of_objects: self.header.number_of_objects,
+        }
+    }
+
+    /// Object header information of the specified object.
+    pub async fn metadata(&self, id: u64) -> Result<ObjectMetadata, SourceError> {
+        if let Some(info) = self.mapping.get(id).and_then(|m| m.info.as_ref()) {
+            return Ok(ObjectMetadata::from(info));
+        }
+        let offset = self.object_offset(id)?;
+        let file = self.file.clone();
+        tokio::task::spawn_blocking(move || read_object_metadata(&file, offset))
+            .await
+            .map_err(|e| SourceError::IOError(e.into()))?
+            .map_err(SourceError::IOError)
+    }
+
+    /// Reads the requested object from the database.
+    ///
+    /// The object is read and decompressed as a stream on the blocking thread pool.
+    ///
+    /// Note: Requesting the same object multiple times results in reading and interpreting the
+    /// data each time.
+    pub async fn get_object(&self, id: u64) -> Result<Object, SourceError> {
+        let offset = self.object_offset(id)?;
+        let file = self.file.clone();
+        let raw_object = tokio::task::spawn_blocking(move || read_object(&file, offset))
+            .await
+            .map_err(|e| SourceError::IOError(e.into()))?
+            .map_err(|e| match e {
+                ObjectDecodeError::IOError(e) => SourceError::IOError(e),
+                e => SourceError::InvalidObject(e),
+            })?;
+        Ok(Object::from(raw_object))
+    }
+
+    /// Reads the requested object and parses it as `T`, see
+    /// [`DatabaseSource::get`](crate::DatabaseSource::get).
+    pub async fn get<T: ObjectFormat>(&self, id: u64) -> Result<T, GetObjectError> {
+        let object = self.get_object(id).await?;
+        T::try_from_object(&object).map_err(|error| GetObjectError::InvalidFormat { id, error })
+    }
+
+    /// Reads every object in the format of `T`, see
+    /// [`DatabaseSource::find_all`](crate::DatabaseSource::find_all).
+    pub async fn find_all<T: ObjectFormat>(&self) -> Result<Vec<(u64, T)>, GetObjectError> {
+        let mut ids = self.object_ids();
+        ids.sort_unstable();
+        let mut objects = Vec::new();
+        for id in ids {
+            let metadata = self.metadata(id).await?;
+            if metadata.format == T::FORMAT && T::ENTRY_TYPES.contains(&metadata.entry_type) {
+                objects.push((id, self.get(id).await?));
+            }
+        }
+        Ok(objects)
+    }
+
+    fn object_offset(&self, id: u64) -> Result<u64, SourceError> {
+        self.mapping
+            .get(id)
+            .map(|m| m.offset)
+            .ok_or(SourceError::NotFound(id))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::async_database::AsyncLazyDatabase;
+    use crate::database::ObjectCompressionType;
+    use crate::formats::sha256_set::SHA256SetObject;
+    use crate::formats::simple_tlsh::{SimpleTLSHEntryType, SimpleTLSHObject};
+    use crate::raw_database_file::DatabaseParseError::InvalidObjectMap;
+    use crate::source::{GetObjectError, SourceError};
+    use crate::{Database, ObjectImpl};
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    pub fn test_async_load() {
+        let mut tlsh_object = SimpleTLSHObject::new(SimpleTLSHEntryType::HEX);
+        tlsh_object.add_hash(
+            "B911A8DACB5B5A06568B6ED299B18014C811DD897E95B720B871B1F5EF7300538187DC".to_string(),
+        );
+        let mut database = Database::new(3);
+        database.add_object(7, tlsh_object.to_object());
+        let path = std::env::temp_dir().join("simbiota_test_async_load.sdb");
+        std::fs::write(&path, database.as_bytes()).unwrap();
+
+        block_on(async {
+            let database = AsyncLazyDatabase::open(&path).await.unwrap();
+            assert_eq!(database.object_ids(), vec![7]);
+            assert!(database.has_object(7));
+            assert_eq!(database.info().database_version, 3);
+
+            let metadata = database.metadata(7).await.unwrap();
+            assert_eq!(metadata.format, 0x0001);
+            assert_eq!(
+                metadata.compression,
+                ObjectCompressionType::NoCompression.get_value()
+            );
+            assert_eq!(metadata.entry_count, Some(1));
+
+            let object = database.get_object(7).await.unwrap();
+            let tlsh_object = SimpleTLSHObject::from_object(object).unwrap();
+            assert_eq!(tlsh_object.get_hashes().len(), 1);
+
+            let not_found = database.get_object(1).await.unwrap_err();
+            assert!(matches!(not_found, SourceError::NotFound(1)));
+
+            let tlsh_object: SimpleTLSHObject = database.get(7).await.unwrap();
+            assert_eq!(tlsh_object.get_hashes().len(), 1);
+            let wrong_format = database.get::<SHA256SetObject>(7).await;
+            assert!(matches!(
+                wrong_format,
+                Err(GetObjectError::InvalidFormat { id: 7, .. })
+            ));
+            let objects = database.find_all::<SimpleTLSHObject>().await.unwrap();
+            assert_eq!(objects.len(), 1);
+            assert_eq!(objects[0].0, 7);
+            assert!(database
+                .find_all::<SHA256SetObject>()
+                .await
+                .unwrap()
+                .is_empty());
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn test_async_oversized_object_map() {
+        let mut data = Database::new(1).as_bytes();
+        let header_len = u32::from_be_bytes(data[16..20].try_into().unwrap()) as usize;
+        data.truncate(header_len);
+        data[8..16].copy_from_slice(&(1u64 << 34).to_be_bytes());
+        let path = std::env::temp_dir().join("simbiota_test_async_oversized_object_map.sdb");
+        std::fs::write(&path, &data).unwrap();
+
+        let result = block_on(AsyncLazyDatabase::open(&path));
+        assert!(matches!(result, Err(InvalidObjectMap(_))));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,5 +1,5 @@
 use simbiota_database::formats::simple_tlsh::SimpleTLSHObject;
-use simbiota_database::{Database, ObjectImpl};
+use simbiota_database::{Database, DatabaseSource};
 use std::io::Read;
 
 fn main() {
@@ -10,8 +10,12 @@ fn main() {
 
     let mut raw_database = Database::from_bytes(db_data.as_slice()).unwrap();
 
-    let obj = raw_database.get_object(0x0001).unwrap();
-    let mut tlsh_obj = SimpleTLSHObject::from_object(obj.clone()).unwrap();
+    let (id, mut tlsh_obj) = raw_database
+        .find_all::<SimpleTLSHObject>()
+        .unwrap()
+        .into_iter()
+        .next()
+        .expect("no SimpleTLSH object in the database");
 
     let mut stdin_lines = String::new();
     std::io::stdin().read_to_string(&mut stdin_lines).unwrap();
@@ -25,7 +29,7 @@ fn main() {
         tlsh_obj.add_hash(line.to_owned());
     }
 
-    raw_database.add_object(0x0001, tlsh_obj.to_object());
+    raw_database.put(id, tlsh_obj);
 
     let bytes = raw_database.as_bytes();
     std::fs::write(dbfile, bytes).unwrap();
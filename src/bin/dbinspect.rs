@@ -1,14 +1,15 @@
 extern crate core;
 
 use clap::{value_parser, Arg, ArgAction, Command};
-use database::database::{Object, ObjectImpl};
-use database::formats;
+use simbiota_database::database::{Object, VARIABLE_ENTRY_SIZE};
+use simbiota_database::formats;
+use simbiota_database::formats::registry;
 use std::io::Read;
 use std::path::PathBuf;
 use std::process::exit;
 
-use database::header::Header;
-use database::raw_database_file::RawDatabaseFile;
+use simbiota_database::header::Header;
+use simbiota_database::raw_database_file::RawDatabaseFile;
 
 fn main() {
     let mut command = Command::new("dbinspect")
@@ -85,7 +86,8 @@ fn main() {
         println!("\tHeader length: {0} ({0:#x})", header.header_len);
 
         if matches.get_flag("extra-header") {
-            if header.version == 1 {
+            // Version 2 and 3 use the v1 extra data
+            if (1..=3).contains(&header.version) {
                 if header.extra_data.len() < 16 {
                     println!("\tExtra header data(v1):\n\t\t<Invalid v1 header>");
                 } else {
@@ -113,13 +115,33 @@ fn main() {
             .expect("invalid database file");
         println!();
         println!("Object map:");
-        println!("\t{:^16}   {:^16}", "ID", "Offset");
-        for mapping in object_map.mappings {
+        let has_info = object_map.mappings().iter().any(|m| m.info.is_some());
+        if has_info {
+            println!(
+                "\t{:^16}   {:^16}   {:^6}   {:^6}   {:^10}   {:^10}   {:^16}   {:^16}",
+                "ID", "Offset", "Format", "Compr.", "Entry type", "Entry size", "Length", "Entries"
+            );
+        } else {
+            println!("\t{:^16}   {:^16}", "ID", "Offset");
+        }
+        for mapping in object_map.mappings() {
             let is_invalid = mapping.offset as usize >= bytes.len();
+            let info = mapping.info.as_ref().map_or(String::new(), |info| {
+                format!(
+                    "   {:#06x}   {:#06x}   {:#010x}   {:>10}   {:>16}   {:>16}",
+                    info.format,
+                    info.compression,
+                    info.entry_type,
+                    get_entry_size_text(info.entry_size),
+                    info.length,
+                    info.entry_count
+                )
+            });
             println!(
-                "\t{:016x}   {:016x}{}",
+                "\t{:016x}   {:016x}{}{}",
                 mapping.id,
                 mapping.offset,
+                info,
                 if is_invalid { " - INVALID" } else { "" }
             );
         }
@@ -129,15 +151,17 @@ fn main() {
         println!("Object headers:");
         let file = RawDatabaseFile::try_from(bytes.as_slice()).expect("invalid database file");
         for (id, object) in &file.objects {
-            let parsed_object = formats::get_concrete_object(Object::from(object));
+            let format = registry::find_format(object.format, object.entry_type);
+            let is_valid = formats::get_concrete_object(Object::from(object)).is_some();
             println!("\tObject #{}", id);
             println!(
-                "\t\tFormat: {:#x} ({})",
+                "\t\tFormat: {:#x} ({}{})",
                 object.format,
-                if let Some(object_impl) = parsed_object {
-                    get_object_name(object_impl)
+                format.map_or("unknown", |f| f.name),
+                if format.is_some() && !is_valid {
+                    ", INVALID"
                 } else {
-                    "unknown"
+                    ""
                 }
             );
             println!(
@@ -146,18 +170,23 @@ fn main() {
                 get_compression_text(object.compression)
             );
             println!("\t\tEntry type: {:#x}", object.entry_type);
-            println!("\t\tEntry size: {:#x} ({0:})", object.entry_size);
+            println!(
+                "\t\tEntry size: {:#x} ({})",
+                object.entry_size,
+                get_entry_size_text(object.entry_size)
+            );
             println!("\t\tLength: {:#x} ({0:})", object.length);
             println!();
         }
     }
 }
 
-fn get_object_name<T>(_: T) -> &'static str
-where
-    T: ObjectImpl,
-{
-    T::NAME
+fn get_entry_size_text(entry_size: u16) -> String {
+    if entry_size == VARIABLE_ENTRY_SIZE {
+        "variable".to_string()
+    } else {
+        entry_size.to_string()
+    }
 }
 
 fn get_compression_text(compression: u16) -> &'static str {
@@ -0,0 +1,126 @@
+use clap::{value_parser, Arg, ArgAction, Command};
+use simbiota_database::migration::{Migration, MigrationPlan};
+use simbiota_database::Database;
+use std::path::PathBuf;
+use std::process::exit;
+
+fn parse_migration(value: &str) -> Result<Migration, String> {
+    match value.split_once('=') {
+        None if value == "simple-to-raw" => Ok(Migration::SimpleTLSHToRaw),
+        None if value == "simple-to-hex" => Ok(Migration::SimpleTLSHToHex),
+        None if value == "remove-distance" => Ok(Migration::RemoveDistance),
+        None if value == "remove-labels" => Ok(Migration::RemoveLabels),
+        Some(("add-distance", distance)) => distance
+            .parse()
+            .map(|distance| Migration::AddDistance { distance })
+            .map_err(|_| format!("invalid distance: {}", distance)),
+        _ => Err(format!("unknown migration: {}", value)),
+    }
+}
+
+fn main() {
+    let matches = Command::new("dbmigrate")
+        .version("0.0.1")
+        .author("Ukatemi Technologies Zrt.")
+        .about("Convert the objects of SIMBIoTA database files between formats")
+        .after_help(
+            "Migrations, applied in the specified order:\n  \
+            simple-to-raw       SimpleTLSH hex entries to binary\n  \
+            simple-to-hex       SimpleTLSH binary entries to hex\n  \
+            add-distance=<N>    ColoredTLSH to ColoredTLSHWithDistance, with distance N\n  \
+            remove-distance     ColoredTLSHWithDistance to ColoredTLSH\n  \
+            remove-labels       Labelled ColoredTLSH(WithDistance) entries to unlabelled",
+        )
+        .arg(
+            Arg::new("migration")
+                .short('m')
+                .long("migration")
+                .help("Migration to apply, can be repeated")
+                .required(true)
+                .action(ArgAction::Append)
+                .value_parser(parse_migration),
+        )
+        .arg(
+            Arg::new("object")
+                .short('i')
+                .long("object")
+                .help("Only migrate the object with the ID, every migration must apply to it. Can be repeated")
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .help("Write the migrated database to this file instead of the input")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .short('n')
+                .long("dry-run")
+                .help("Only list the objects that would be migrated")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("database file")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .get_matches();
+
+    let file_path = matches.get_one::<PathBuf>("database file").unwrap();
+    let bytes = match std::fs::read(file_path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("error: '{}': {}", file_path.display(), e);
+            exit(1);
+        }
+    };
+    let mut database = match Database::from_bytes(bytes.as_slice()) {
+        Ok(database) => database,
+        Err(e) => {
+            eprintln!("error: invalid database file: {:?}", e);
+            exit(1);
+        }
+    };
+
+    let object_ids: Option<Vec<u64>> = matches
+        .get_many::<u64>("object")
+        .map(|ids| ids.copied().collect());
+    let mut plan = MigrationPlan::new();
+    for migration in matches.get_many::<Migration>("migration").unwrap() {
+        match &object_ids {
+            Some(ids) => plan.add_object_migration(*migration, ids),
+            None => plan.add_migration(*migration),
+        }
+    }
+
+    let migrated = match database.migrate(&plan) {
+        Ok(migrated) => migrated,
+        Err(e) => {
+            eprintln!("error: migration failed: {:?}", e);
+            exit(1);
+        }
+    };
+    for m in &migrated {
+        println!(
+            "object {:#x}: {:?}",
+            m.object_id,
+            plan.get_steps()[m.step].migration
+        );
+    }
+    if migrated.is_empty() {
+        println!("No objects to migrate");
+    }
+    let output = matches.get_one::<PathBuf>("output");
+    if matches.get_flag("dry-run") || (migrated.is_empty() && output.is_none()) {
+        return;
+    }
+
+    let output = output.unwrap_or(file_path);
+    if let Err(e) = std::fs::write(output, database.as_bytes()) {
+        eprintln!("error: '{}': {}", output.display(), e);
+        exit(1);
+    }
+}
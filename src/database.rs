@@ -4,25 +4,19 @@
 //! # Example: Load hashes from a database file
 //! ```rust no_run
 //! use std::path::Path;
-//! use database::database::{Database, ObjectImpl};
-//! use database::formats;
-//! use formats::simple_tlsh::SimpleTLSHObject;
-//! use simbiota_database::{Database, ObjectImpl};
+//! use simbiota_database::{Database, DatabaseSource};
 //! use simbiota_database::formats::simple_tlsh::SimpleTLSHObject;
 //!
 //! let file_data = std::fs::read(Path::new("database_file.sdb")).unwrap();
 //! let database = Database::from_bytes(file_data.as_slice()).expect("failed to load database");
-//! let object = database.get_object(0x0001).expect("object not found");
-//! let tlsh_list = SimpleTLSHObject::from_object(object.clone()).expect("failed to parse object");
-//! println!("Entries: {}", tlsh_list.get_hashes());
+//! let tlsh_list: SimpleTLSHObject = database.get(0x0001).expect("failed to get object");
+//! println!("Entries: {:?}", tlsh_list.get_hashes());
 //! ```
 //!
 //! # Example: Create a new database and save it to a file
 //! ```rust no_run
 //! use std::path::Path;
-//! use database::database::{Database, ObjectImpl};
-//! use database::formats::simple_tlsh::{SimpleTLSHEntryType, SimpleTLSHObject};
-//! use simbiota_database::{Database, ObjectImpl};
+//! use simbiota_database::Database;
 //! use simbiota_database::formats::simple_tlsh::{SimpleTLSHEntryType, SimpleTLSHObject};
 //!
 //! let mut tlsh_object = SimpleTLSHObject::new(SimpleTLSHEntryType::HEX);
@@ -42,7 +36,7 @@
 //!         "3DB633814E9F2046252E5DD0E10FFBC4A54FEB96D02B4A158B33CE97B76888931937B7".to_string(),
 //!     );
 //!     let mut database = Database::new(1);
-//!     database.add_object(1, tlsh_object.to_object());
+//!     database.put(1, tlsh_object);
 //!     let bytes = database.as_bytes();
 //!     std::fs::write(Path::new("test_files/generated1.sdb"), bytes.clone())
 //!         .expect("failed to write file");
@@ -50,13 +44,22 @@
 
 use crate::database::LazyParsingError::{InvalidObject, NotFound};
 use crate::database::ObjectCompressionType::{NoCompression, DEFLATE};
-use crate::header::Header;
+use crate::formats::label_map::{self, ResolvedLabel};
+use crate::formats::string_table;
+use crate::hash::Sha256;
+use crate::header::{Header, HeaderDecodeError, SUPPORTED_VERSIONS};
+use crate::migration::{self, MigratedObject, MigrationError, MigrationPlan};
 use crate::object::{ObjectDecodeError, RawObject};
-use crate::object_map::{ObjectMap, ObjectMapping};
+use crate::object_map::{ObjectMap, ObjectMapping, ObjectMappingInfo};
 use crate::raw_database_file::DatabaseParseError::{
     FileOpenFailed, HeaderParsingError, IOError, InvalidHeader, InvalidObjectMap,
+    UnsupportedVersion,
 };
 use crate::raw_database_file::{DatabaseParseError, RawDatabaseFile};
+use crate::scan::{self, ScanResult};
+use crate::source::{DatabaseInfo, DatabaseSource, ObjectMetadata, SourceError};
+use crate::tlsh::Tlsh;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
@@ -68,7 +71,7 @@ use std::path::Path;
 use std::time::UNIX_EPOCH;
 
 /// Compression type setting for objects.
-#[derive(Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ObjectCompressionType {
     /// Entries are not compressed.
     NoCompression,
@@ -105,14 +108,94 @@ pub trait ObjectImpl: Sized {
     /// Parse the data from a generic object. The implementation must check whether the generic
     /// object's format and entry type is valid.
     ///
-    /// Returns none if the object is not in the implementation's format.
+    /// Returns none if the object is not in the implementation's format. See
+    /// [`ObjectFormat::try_from_object`] for the reason of the failure.
     fn from_object(obj: Object) -> Option<Self>
     where
         Self: Sized;
 }
 
+/// Error representing the reasons an object is rejected by an [`ObjectFormat`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatError {
+    /// The object is in a different format.
+    WrongFormat { expected: u16, found: u16 },
+    /// The entry type is not in [`ObjectFormat::ENTRY_TYPES`].
+    UnsupportedEntryType(u16),
+    /// The entry size does not match the entry type.
+    InvalidEntrySize { expected: u16, found: u16 },
+    /// The entry at the index is malformed, missing, or violates a constraint of the format,
+    /// e.g. it is out of order.
+    MalformedEntry(usize),
+}
+
+/// Fallible counterpart of [`ObjectImpl::from_object`], parsing a borrowed object.
+///
+/// Implementations are also convertible with `TryFrom<&Object>`.
+pub trait ObjectFormat: ObjectImpl {
+    /// Format ID of the objects.
+    const FORMAT: u16;
+    /// The entry types the implementation can parse.
+    const ENTRY_TYPES: &'static [u16];
+
+    /// Parse the data from a generic object, returning the reason if the object is invalid.
+    fn try_from_object(obj: &Object) -> Result<Self, FormatError>;
+
+    /// Check the format and the entry type of an object, without parsing the entries.
+    fn check_object(obj: &Object) -> Result<(), FormatError> {
+        if obj.format != Self::FORMAT {
+            return Err(FormatError::WrongFormat {
+                expected: Self::FORMAT,
+                found: obj.format,
+            });
+        }
+        if !Self::ENTRY_TYPES.contains(&obj.entry_type) {
+            return Err(FormatError::UnsupportedEntryType(obj.entry_type));
+        }
+        Ok(())
+    }
+}
+
+/// Check the entry size of an object, and the length of every entry if the size is fixed.
+pub(crate) fn check_entry_size(obj: &Object, expected: u16) -> Result<(), FormatError> {
+    if obj.entry_size != expected {
+        return Err(FormatError::InvalidEntrySize {
+            expected,
+            found: obj.entry_size,
+        });
+    }
+    if expected != VARIABLE_ENTRY_SIZE {
+        if let Some(index) = obj.data.iter().position(|e| e.len() != expected as usize) {
+            return Err(FormatError::MalformedEntry(index));
+        }
+    }
+    Ok(())
+}
+
+/// Implement `TryFrom<&Object>` with [`ObjectFormat::try_from_object`].
+macro_rules! impl_try_from_object {
+    ($($object:ty),*) => {
+        $(
+            impl TryFrom<&$crate::database::Object> for $object {
+                type Error = $crate::database::FormatError;
+
+                fn try_from(obj: &$crate::database::Object) -> Result<Self, Self::Error> {
+                    <$object as $crate::database::ObjectFormat>::try_from_object(obj)
+                }
+            }
+        )*
+    };
+}
+pub(crate) use impl_try_from_object;
+
+/// Entry size of objects with variable-length entries.
+///
+/// The data of these objects starts with the number of entries and the end offset of each entry
+/// in the data region, followed by the data region.
+pub const VARIABLE_ENTRY_SIZE: u16 = 0;
+
 /// Generic database object.
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct Object {
     /// Format of this object. Used for decoding the data to a specific [`ObjectImpl`].
     pub(crate) format: u16,
@@ -121,12 +204,59 @@ pub struct Object {
     /// What kind of entries are stored inside this object. One format can support multiple
     /// entry types (e.g hex and binary hashes).
     pub(crate) entry_type: u16,
-    /// Size of each entry, used for decoding.
+    /// Size of each entry, used for decoding. [`VARIABLE_ENTRY_SIZE`] if the entries can have
+    /// different lengths.
     pub(crate) entry_size: u16,
     /// Raw data of each entry.
     pub(crate) data: Vec<Vec<u8>>,
 }
 
+impl Object {
+    /// Create an object from its entries, e.g. in [`ObjectImpl::to_object`] of a custom format.
+    pub fn new(
+        format: u16,
+        compression_type: ObjectCompressionType,
+        entry_type: u16,
+        entry_size: u16,
+        data: Vec<Vec<u8>>,
+    ) -> Self {
+        Self {
+            format,
+            compression_type,
+            entry_type,
+            entry_size,
+            data,
+        }
+    }
+
+    pub fn format(&self) -> u16 {
+        self.format
+    }
+
+    pub fn compression_type(&self) -> ObjectCompressionType {
+        self.compression_type
+    }
+
+    pub fn entry_type(&self) -> u16 {
+        self.entry_type
+    }
+
+    /// Size of each entry, [`VARIABLE_ENTRY_SIZE`] if the entries can have different lengths.
+    pub fn entry_size(&self) -> u16 {
+        self.entry_size
+    }
+
+    /// Raw data of each entry.
+    pub fn data(&self) -> &[Vec<u8>] {
+        &self.data
+    }
+
+    /// Consume the object, returning the raw data of each entry.
+    pub fn into_data(self) -> Vec<Vec<u8>> {
+        self.data
+    }
+}
+
 impl From<&RawObject> for Object {
     /// Create a [`Object`] from a [`RawObject`] reference, _copying_ the data.
     fn from(value: &RawObject) -> Self {
@@ -168,8 +298,12 @@ pub enum LazyParsingError {
 /// For better access time, use [`Database`].
 pub struct LazyLoadedDatabase {
     file: File,
-    _header: Header,
-    mapping: ObjectMap,
+    header: Header,
+    /// The object map, if it is kept in memory. Otherwise the sorted on-disk object map is
+    /// searched on each access.
+    mapping: Option<ObjectMap>,
+    last_updated: u64,
+    database_version: u64,
 }
 
 impl LazyLoadedDatabase {
@@ -181,32 +315,83 @@ impl LazyLoadedDatabase {
     /// Create a new [`LazyLoadedDatabase`] from a specified file path. The header and object map
     /// are loaded and kept in memory, but no objects are loaded.
     pub fn new(file: &Path) -> Result<Self, DatabaseParseError> {
+        let mut database = Self::open(file)?;
+        let file_len = database.file.metadata().map_err(IOError)?.len();
+        let mapping_size = ObjectMap::checked_length(
+            database.header.version,
+            database.header.number_of_objects,
+            database.header.header_len,
+            file_len,
+        )
+        .map_err(InvalidObjectMap)?;
+        let mut mapping_data = vec![0u8; mapping_size];
+        read_exact_offset(
+            &database.file,
+            mapping_data.as_mut_slice(),
+            database.header.header_len as u64,
+        )
+        .map_err(IOError)?;
+        let mapping = ObjectMap::try_from_version(
+            mapping_data.as_slice(),
+            database.header.number_of_objects,
+            database.header.version,
+        )
+        .map_err(InvalidObjectMap)?;
+        database.mapping = Some(mapping);
+        Ok(database)
+    }
+
+    /// Create a new [`LazyLoadedDatabase`] that only keeps the header in memory.
+    ///
+    /// Objects are located with a binary search over the on-disk object map, using a few
+    /// positioned reads per lookup. This requires a sorted object map, available from version 2
+    /// database files.
+    pub fn new_minimal(file: &Path) -> Result<Self, DatabaseParseError> {
+        let database = Self::open(file)?;
+        if database.header.version < 2 {
+            return Err(UnsupportedVersion(database.header.version));
+        }
+        Ok(database)
+    }
+
+    /// Opens the file and reads the header.
+    fn open(file: &Path) -> Result<Self, DatabaseParseError> {
         let mut file = std::fs::File::open(file).map_err(FileOpenFailed)?;
 
         // Read minimal header
         let mut minimal_header_buf = [0u8; 0x20];
         file.read_exact(&mut minimal_header_buf).map_err(IOError)?;
         let length = u32::from_be_bytes((&minimal_header_buf[16..20]).try_into().unwrap());
-        let mut header_data = Vec::with_capacity(length as usize);
+        if length as u64 > file.metadata().map_err(IOError)?.len() {
+            return Err(InvalidHeader(HeaderDecodeError::TooShort));
+        }
+        let mut header_data = vec![0u8; length as usize];
         read_exact_offset(&file, header_data.as_mut_slice(), 0).map_err(IOError)?;
 
         let header = Header::try_from(header_data.as_slice()).map_err(InvalidHeader)?;
-        let mapping_size = 16 * header.number_of_objects;
-        let mut mapping_data = Vec::with_capacity(mapping_size as usize);
-        read_exact_offset(&file, mapping_data.as_mut_slice(), header.header_len as u64)
-            .map_err(IOError)?;
-        let mapping = ObjectMap::try_from(mapping_data.as_slice(), header.number_of_objects)
-            .map_err(InvalidObjectMap)?;
+        let (last_updated, database_version) = parse_v1_extra_data(&header.extra_data)?;
         Ok(Self {
             file,
-            _header: header,
-            mapping,
+            header,
+            mapping: None,
+            last_updated,
+            database_version,
         })
     }
 
     /// Check if the database contains a specified object.
+    ///
+    /// Note: If the object map is not in memory and reading it fails, this returns `false`. Use
+    /// [`try_has_object`](Self::try_has_object) to tell the two apart.
     pub fn has_object(&self, id: u64) -> bool {
-        self.mapping.mappings.iter().any(|m| m.id == id)
+        matches!(self.find_mapping(id), Ok(Some(_)))
+    }
+
+    /// Check if the database contains a specified object.
+    ///
+    /// Fails if the object map is not in memory and reading it from the file fails.
+    pub fn try_has_object(&self, id: u64) -> std::io::Result<bool> {
+        Ok(self.find_mapping(id)?.is_some())
     }
 
     /// Reads the requested object from the database if possible.
@@ -214,25 +399,90 @@ impl LazyLoadedDatabase {
     /// Note: Requesting the same object multiple times results in reading and interpreting the
     /// data each time.
     pub fn get_object(&self, id: u64) -> Result<Object, LazyParsingError> {
-        if !self.has_object(id) {
-            return Err(NotFound);
-        }
-        let mapping = self.mapping.mappings.iter().find(|m| m.id == id).unwrap();
-        let mut temp_obj_header = [0u8; 16];
-        read_exact_offset(&self.file, &mut temp_obj_header, mapping.offset)
-            .map_err(LazyParsingError::IOError)?;
-
-        let len = u64::from_be_bytes((&temp_obj_header[8..16]).try_into().unwrap());
-        let mut object_data = Vec::with_capacity(len as usize);
-        read_exact_offset(&self.file, object_data.as_mut_slice(), mapping.offset)
-            .map_err(LazyParsingError::IOError)?;
-
-        let raw_object = RawObject::try_from(object_data)
-            .map_err(InvalidObject)
-            .unwrap();
+        let mapping = self
+            .find_mapping(id)
+            .map_err(LazyParsingError::IOError)?
+            .ok_or(NotFound)?;
+        let raw_object = read_object(&self.file, mapping.offset).map_err(|e| match e {
+            ObjectDecodeError::IOError(e) => LazyParsingError::IOError(e),
+            e => InvalidObject(e),
+        })?;
         let object = Object::from(raw_object);
         Ok(object)
     }
+
+    /// Find the mapping of an object, either in the loaded object map or on disk.
+    fn find_mapping(&self, id: u64) -> std::io::Result<Option<ObjectMapping>> {
+        if let Some(mapping) = &self.mapping {
+            return Ok(mapping.get(id).cloned());
+        }
+        ObjectMap::search_sorted(self.header.number_of_objects, id, |index| {
+            self.read_mapping(index)
+        })
+    }
+
+    /// Reads a single mapping from the on-disk object map.
+    fn read_mapping(&self, index: u64) -> std::io::Result<ObjectMapping> {
+        let mapping_size = ObjectMap::mapping_size(self.header.version);
+        let mut mapping_data = vec![0u8; mapping_size];
+        let offset = self.header.header_len as u64 + mapping_size as u64 * index;
+        read_exact_offset(&self.file, &mut mapping_data, offset)?;
+        ObjectMapping::try_from(mapping_data.as_slice())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{:?}", e)))
+    }
+}
+
+impl DatabaseSource for LazyLoadedDatabase {
+    /// Note: If the object map is not in memory, it is read from the file on each call.
+    fn object_ids(&self) -> Result<Vec<u64>, SourceError> {
+        if let Some(mapping) = &self.mapping {
+            return Ok(mapping.mappings().iter().map(|m| m.id).collect());
+        }
+        (0..self.header.number_of_objects)
+            .map(|index| self.read_mapping(index).map(|m| m.id))
+            .collect::<std::io::Result<_>>()
+            .map_err(SourceError::IOError)
+    }
+
+    fn has_object(&self, id: u64) -> Result<bool, SourceError> {
+        self.try_has_object(id).map_err(SourceError::IOError)
+    }
+
+    fn get_object(&self, id: u64) -> Result<Cow<'_, Object>, SourceError> {
+        LazyLoadedDatabase::get_object(self, id)
+            .map(Cow::Owned)
+            .map_err(|e| SourceError::from_lazy(e, id))
+    }
+
+    fn info(&self) -> DatabaseInfo {
+        DatabaseInfo {
+            file_version: self.header.version,
+            database_version: self.database_version,
+            last_updated: self.last_updated,
+            number_of_objects: self.header.number_of_objects,
+        }
+    }
+
+    fn metadata(&self, id: u64) -> Result<ObjectMetadata, SourceError> {
+        let mapping = self
+            .find_mapping(id)
+            .map_err(SourceError::IOError)?
+            .ok_or(SourceError::NotFound(id))?;
+        if let Some(info) = &mapping.info {
+            return Ok(ObjectMetadata::from(info));
+        }
+        read_object_metadata(&self.file, mapping.offset).map_err(SourceError::IOError)
+    }
+}
+
+impl SourceError {
+    fn from_lazy(value: LazyParsingError, id: u64) -> Self {
+        match value {
+            LazyParsingError::IOError(e) => SourceError::IOError(e),
+            NotFound => SourceError::NotFound(id),
+            InvalidObject(e) => SourceError::InvalidObject(e),
+        }
+    }
 }
 
 /// High-level interface for a database.
@@ -241,8 +491,9 @@ impl LazyLoadedDatabase {
 /// For resource-constrained environments, use [`LazyLoadedDatabase`].
 pub struct Database {
     objects: HashMap<u64, Object>,
-    _last_updated: u64,
+    last_updated: u64,
     database_version: u64,
+    file_version: u32,
 }
 
 impl Database {
@@ -250,9 +501,27 @@ impl Database {
     pub fn new(database_version: u64) -> Self {
         Database {
             objects: HashMap::new(),
-            _last_updated: 0,
+            last_updated: 0,
             database_version,
+            file_version: 1,
+        }
+    }
+
+    /// Set the file format version used when saving the database. New databases are saved as
+    /// version 1 files, loaded ones keep their original version.
+    ///
+    /// Version 2 files have a sorted object map, so [`LazyLoadedDatabase::new_minimal`] can
+    /// look up objects without loading the object map. Version 3 files also store the format,
+    /// compression, entry type, length and entry count of each object in the object map.
+    ///
+    /// Fails with [`DatabaseParseError::UnsupportedVersion`] if the version is not supported,
+    /// the file version is left unchanged in that case.
+    pub fn set_file_version(&mut self, version: u32) -> Result<(), DatabaseParseError> {
+        if !SUPPORTED_VERSIONS.contains(&version) {
+            return Err(UnsupportedVersion(version));
         }
+        self.file_version = version;
+        Ok(())
     }
 
     /// Add an object with the specified id to the database.
@@ -264,6 +533,12 @@ impl Database {
         self.objects.insert(id, obj);
     }
 
+    /// Convert an object to a generic object and store it with the specified ID, replacing the
+    /// object stored with the same ID. See [`DatabaseSource::get`] for reading it back.
+    pub fn put<T: ObjectImpl>(&mut self, id: u64, obj: T) {
+        self.add_object(id, obj.to_object());
+    }
+
     /// Get a stored object from the database by its ID.
     pub fn get_object(&self, id: u64) -> Option<&Object> {
         self.objects.get(&id)
@@ -274,33 +549,79 @@ impl Database {
         self.objects.get_mut(&id)
     }
 
+    /// Get a string by its index from the string table object with the specified ID.
+    ///
+    /// Returns none if the object is not a string table or the string does not exist.
+    pub fn get_string(&self, string_table_id: u64, index: u32) -> Option<&str> {
+        string_table::lookup(self.get_object(string_table_id)?, index)
+    }
+
+    /// Get the family name and tags of a sample from the label map object with the specified
+    /// ID, e.g. for the SHA256 hash of a matching TLSH entry. The strings are resolved from the
+    /// string table referenced by the label map.
+    ///
+    /// Returns none if the sample has no label, or the label map or the string table is invalid.
+    pub fn get_label(&self, label_map_id: u64, sha256_hash: &Sha256) -> Option<ResolvedLabel> {
+        let (string_table_id, label) =
+            label_map::lookup(self.get_object(label_map_id)?, sha256_hash)?;
+        let string_table = self.get_object(string_table_id)?;
+        let resolve = |index: u32| string_table::lookup(string_table, index).map(str::to_string);
+        Some(ResolvedLabel {
+            family: resolve(label.family)?,
+            tags: label
+                .tags
+                .into_iter()
+                .map(resolve)
+                .collect::<Option<Vec<String>>>()?,
+        })
+    }
+
+    /// Check a sample against every detection object, suppressing the hits if an allowlist
+    /// object covers the sample. See the [`scan`](crate::scan) module for the supported objects.
+    ///
+    /// `tlsh_threshold` is the maximum distance for the objects without per-entry thresholds.
+    pub fn scan(
+        &self,
+        sha256_hash: &Sha256,
+        tlsh: Option<&Tlsh>,
+        tlsh_threshold: u32,
+    ) -> ScanResult {
+        scan::scan(
+            self.objects.iter().map(|(id, obj)| (*id, obj)),
+            sha256_hash,
+            tlsh,
+            tlsh_threshold,
+        )
+    }
+
+    /// Convert objects between formats and entry types, see the [`migration`](crate::migration)
+    /// module. Returns the converted objects in the order of the conversions.
+    ///
+    /// The database is not modified if the migration fails.
+    pub fn migrate(&mut self, plan: &MigrationPlan) -> Result<Vec<MigratedObject>, MigrationError> {
+        let (objects, migrated) = migration::migrate(&self.objects, plan)?;
+        self.objects.extend(objects);
+        Ok(migrated)
+    }
+
     /// Loads the database from a byte stream.
     ///
     /// Parses the header and loads all objects into memory.
     pub fn from_bytes(data: &[u8]) -> Result<Self, DatabaseParseError> {
         let raw_database = RawDatabaseFile::try_from(data)?;
-        let extra_data = &raw_database.header.extra_data;
-
-        if extra_data.len() < 16 {
-            return Err(HeaderParsingError("missing v1 extra data"));
-        }
-
-        let timestamp_bytes = &extra_data[0..8];
-        let version_bytes = &extra_data[8..16];
-
-        let timestamp = u64::from_be_bytes(timestamp_bytes.try_into().unwrap());
-        let version = u64::from_be_bytes(version_bytes.try_into().unwrap());
+        let (timestamp, version) = parse_v1_extra_data(&raw_database.header.extra_data)?;
 
         let mut objects = HashMap::new();
-        for (id, raw_obj) in raw_database.objects.iter() {
-            let obj = Object::from(raw_obj);
-            objects.insert(*id, obj);
+        for mapping in raw_database.object_map.mappings() {
+            let obj = Object::from(&raw_database.objects[&mapping.id]);
+            objects.insert(mapping.id, obj);
         }
 
         Ok(Self {
             objects,
-            _last_updated: timestamp,
+            last_updated: timestamp,
             database_version: version,
+            file_version: raw_database.header.version,
         })
     }
 
@@ -319,37 +640,59 @@ impl Database {
                 .for_each(|v| data.push(*v));
             data
         };
-        let header = Header::new(self.objects.len() as u64, extra_data);
+        let header = Header::with_version(self.file_version, self.objects.len() as u64, extra_data);
         let mut output_data = Vec::from(header);
         let header_len = output_data.len();
         let mut mappings: Vec<ObjectMapping> = Vec::new();
         let mut object_data = Vec::new();
 
-        for (id, object) in &self.objects {
+        // Objects are always stored in ID order, as required by version 2
+        let mut ids: Vec<&u64> = self.objects.keys().collect();
+        ids.sort();
+        for id in ids {
+            let object = &self.objects[id];
             let mut raw_object = RawObject::new(
                 object.format,
                 object.compression_type.get_value(),
                 object.entry_type,
                 object.entry_size,
             );
-            raw_object.data = object.data.clone();
+            object
+                .data
+                .iter()
+                .for_each(|entry| raw_object.add_data(entry.clone()));
             let pre_offset = object_data.len();
-            if pre_offset & 16 != 0 {
+            if pre_offset % 16 != 0 {
                 panic!("someone f-d up the padding");
             }
-            mappings.push(ObjectMapping::new(*id, pre_offset as u64));
+            let entry_count = raw_object.data.len() as u64;
             let mut out_vec = Vec::from(raw_object);
+            if self.file_version >= 3 {
+                let header = RawObject::parse_header(&out_vec);
+                let info = ObjectMappingInfo {
+                    format: header.format,
+                    compression: header.compression,
+                    entry_type: header.entry_type,
+                    entry_size: header.entry_size,
+                    length: header.length,
+                    entry_count,
+                };
+                mappings.push(ObjectMapping::with_info(*id, pre_offset as u64, info));
+            } else {
+                mappings.push(ObjectMapping::new(*id, pre_offset as u64));
+            }
             object_data.append(&mut out_vec);
         }
 
         // Patch mappings
-        let offset = header_len as u64 + (16 * mappings.len()) as u64;
+        let mapping_size = ObjectMap::mapping_size(self.file_version);
+        let offset = header_len as u64 + (mapping_size * mappings.len()) as u64;
         for mapping in mappings.iter_mut() {
             mapping.offset += offset;
         }
 
         let mut object_map = ObjectMap::new();
-        object_map.mappings = mappings;
+        mappings.into_iter().for_each(|m| object_map.push(m));
         let mut mapping_vec = Vec::from(object_map);
         output_data.append(&mut mapping_vec);
         output_data.append(&mut object_data);
@@ -357,9 +700,209 @@ impl Database {
     }
 }
 
+impl DatabaseSource for Database {
+    fn object_ids(&self) -> Result<Vec<u64>, SourceError> {
+        Ok(self.objects.keys().copied().collect())
+    }
+
+    fn has_object(&self, id: u64) -> Result<bool, SourceError> {
+        Ok(self.objects.contains_key(&id))
+    }
+
+    fn get_object(&self, id: u64) -> Result<Cow<'_, Object>, SourceError> {
+        Database::get_object(self, id)
+            .map(Cow::Borrowed)
+            .ok_or(SourceError::NotFound(id))
+    }
+
+    fn info(&self) -> DatabaseInfo {
+        DatabaseInfo {
+            file_version: self.file_version,
+            database_version: self.database_version,
+            last_updated: self.last_updated,
+            number_of_objects: self.objects.len() as u64,
+        }
+    }
+
+    fn metadata(&self, id: u64) -> Result<ObjectMetadata, SourceError> {
+        let object = Database::get_object(self, id).ok_or(SourceError::NotFound(id))?;
+        Ok(ObjectMetadata {
+            format: object.format,
+            compression: object.compression_type.get_value(),
+            entry_type: object.entry_type,
+            entry_size: object.entry_size,
+            length: None,
+            entry_count: Some(object.data.len() as u64),
+        })
+    }
+}
+
+/// Parses the v1 extra header data, returning the modification timestamp and the database
+/// version.
+pub(crate) fn parse_v1_extra_data(extra_data: &[u8]) -> Result<(u64, u64), DatabaseParseError> {
+    if extra_data.len() < 16 {
+        return Err(HeaderParsingError("missing v1 extra data"));
+    }
+
+    let timestamp_bytes = &extra_data[0..8];
+    let version_bytes = &extra_data[8..16];
+
+    let timestamp = u64::from_be_bytes(timestamp_bytes.try_into().unwrap());
+    let version = u64::from_be_bytes(version_bytes.try_into().unwrap());
+    Ok((timestamp, version))
+}
+
 fn read_exact_offset(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
     #[cfg(target_family = "unix")]
     return file.read_exact_at(buf, offset);
     #[cfg(target_os = "windows")]
     return file.seek_read(buf, offset).map(|s| {});
 }
+
+/// Reads and decodes the object starting at the specified offset.
+pub(crate) fn read_object(file: &File, offset: u64) -> Result<RawObject, ObjectDecodeError> {
+    RawObject::read_from(OffsetReader { file, offset })
+}
+
+/// Reads the header of the object starting at the specified offset.
+pub(crate) fn read_object_metadata(file: &File, offset: u64) -> std::io::Result<ObjectMetadata> {
+    let mut object_header = [0u8; 16];
+    read_exact_offset(file, &mut object_header, offset)?;
+    let object = RawObject::parse_header(&object_header);
+
+    // The entry count can only be calculated from the length if fixed size entries are stored
+    // as-is
+    let entry_count = if object.compression == NoCompression.get_value()
+        && object.entry_size != VARIABLE_ENTRY_SIZE
+    {
+        Some(object.length.saturating_sub(16) / object.entry_size as u64)
+    } else {
+        None
+    };
+
+    Ok(ObjectMetadata {
+        format: object.format,
+        compression: object.compression,
+        entry_type: object.entry_type,
+        entry_size: object.entry_size,
+        length: Some(object.length),
+        entry_count,
+    })
+}
+
+/// [`Read`] implementation using positioned reads, so the same file can be read from multiple
+/// places at once.
+struct OffsetReader<'a> {
+    file: &'a File,
+    offset: u64,
+}
+
+impl Read for OffsetReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        #[cfg(target_family = "unix")]
+        let read = self.file.read_at(buf, self.offset)?;
+        #[cfg(target_os = "windows")]
+        let read = self.file.seek_read(buf, self.offset)?;
+        self.offset += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::database::{
+        FormatError, LazyLoadedDatabase, Object, ObjectCompressionType, ObjectFormat, ObjectImpl,
+        VARIABLE_ENTRY_SIZE,
+    };
+    use crate::formats::bloom_filter::BloomFilterObject;
+    use crate::formats::simple_tlsh::{SimpleTLSHEntryType, SimpleTLSHObject};
+    use crate::formats::tlsh_index::TLSHIndexObject;
+    use crate::source::DatabaseSource;
+    use crate::Database;
+
+    #[test]
+    pub fn test_variable_object_roundtrip() {
+        let entries = vec![
+            b"Mirai".to_vec(),
+            b"Gafgyt".to_vec(),
+            vec![],
+            b"Tsunami".to_vec(),
+        ];
+        for file_version in [1, 3] {
+            let mut database = Database::new(1);
+            database.set_file_version(file_version).unwrap();
+            database.add_object(
+                1,
+                Object {
+                    format: 0x7fff,
+                    compression_type: ObjectCompressionType::NoCompression,
+                    entry_type: 0,
+                    entry_size: VARIABLE_ENTRY_SIZE,
+                    data: entries.clone(),
+                },
+            );
+            let bytes = database.as_bytes();
+
+            let database = Database::from_bytes(bytes.as_slice()).unwrap();
+            assert_eq!(database.get_object(1).unwrap().data, entries);
+
+            let path = std::env::temp_dir().join(format!(
+                "simbiota_test_variable_object_v{}.sdb",
+                file_version
+            ));
+            std::fs::write(&path, bytes).unwrap();
+            let lazy = LazyLoadedDatabase::new(&path).unwrap();
+            assert_eq!(lazy.get_object(1).unwrap().data, entries);
+            let metadata = DatabaseSource::metadata(&lazy, 1).unwrap();
+            assert_eq!(metadata.entry_size, VARIABLE_ENTRY_SIZE);
+            if file_version >= 3 {
+                assert_eq!(metadata.entry_count, Some(4));
+            }
+            lazy.close();
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    pub fn test_object_format() {
+        let mut tlsh_object = SimpleTLSHObject::new(SimpleTLSHEntryType::HEX);
+        tlsh_object.add_hash(
+            "B911A8DACB5B5A06568B6ED299B18014C811DD897E95B720B871B1F5EF7300538187DC".to_string(),
+        );
+        let object = tlsh_object.to_object();
+        assert_eq!(SimpleTLSHObject::check_object(&object), Ok(()));
+        let hashes = SimpleTLSHObject::try_from(&object).unwrap().get_hashes();
+        assert_eq!(hashes.len(), 1);
+
+        assert_eq!(
+            TLSHIndexObject::check_object(&object),
+            Err(FormatError::WrongFormat {
+                expected: 0x0004,
+                found: 0x0001
+            })
+        );
+        assert_eq!(
+            BloomFilterObject::try_from(&object).err(),
+            Some(FormatError::WrongFormat {
+                expected: 0x0006,
+                found: 0x0001
+            })
+        );
+
+        let mut invalid = object.clone();
+        invalid.data[0][0] = 0xff;
+        assert_eq!(
+            SimpleTLSHObject::try_from_object(&invalid).err(),
+            Some(FormatError::MalformedEntry(0))
+        );
+        invalid.entry_type = 1;
+        assert_eq!(
+            SimpleTLSHObject::try_from_object(&invalid).err(),
+            Some(FormatError::InvalidEntrySize {
+                expected: 35,
+                found: 70
+            })
+        );
+        assert!(SimpleTLSHObject::from_object(invalid).is_none());
+    }
+}
@@ -0,0 +1,202 @@
+use crate::database::{
+    check_entry_size, FormatError, Object, ObjectCompressionType, ObjectFormat, ObjectImpl,
+    VARIABLE_ENTRY_SIZE,
+};
+use crate::hash::{Sha256, TlshDigest};
+use crate::tlsh::Tlsh;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AllowlistEntry {
+    /// Allow the sample with this exact SHA256 hash.
+    Sha256(Sha256),
+    /// Allow every sample with a TLSH digest at most `max_distance` away from this standard
+    /// 35-byte digest.
+    Tlsh { tlsh: TlshDigest, max_distance: u16 },
+}
+
+/// An allowlist entry covering a sample, returned by [`AllowlistObject::find`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AllowlistMatch {
+    /// Index of the entry in the allowlist.
+    pub index: usize,
+    pub entry: AllowlistEntry,
+    /// TLSH distance of the sample from the entry, none for SHA256 entries.
+    pub distance: Option<u32>,
+}
+
+/// Object format 0x000C, Allowlist.
+///
+/// Stores known benign samples, e.g. common busybox builds, to suppress false positive
+/// detections. Samples can be allowed by their exact SHA256 hash or by a TLSH digest with a
+/// maximum distance.
+pub struct AllowlistObject {
+    entries: Vec<AllowlistEntry>,
+}
+
+impl AllowlistObject {
+    pub fn empty() -> Self {
+        Self { entries: vec![] }
+    }
+
+    pub fn get_entries(&self) -> &Vec<AllowlistEntry> {
+        &self.entries
+    }
+
+    pub fn add_sha256(&mut self, sha_hash: impl Into<Sha256>) {
+        self.entries.push(AllowlistEntry::Sha256(sha_hash.into()));
+    }
+
+    /// Add a standard 35-byte TLSH digest with the maximum allowed distance.
+    ///
+    /// Panics if the digest is colored.
+    pub fn add_tlsh(&mut self, tlsh_hash: impl Into<TlshDigest>, max_distance: u16) {
+        let tlsh = tlsh_hash.into();
+        assert!(!tlsh.is_colored(), "colored tlsh hash");
+        self.entries
+            .push(AllowlistEntry::Tlsh { tlsh, max_distance });
+    }
+
+    /// Find the entry covering a sample.
+    ///
+    /// An exact SHA256 entry takes precedence, otherwise the TLSH entry closest to the digest
+    /// wins. Returns none if the sample is not allowed.
+    pub fn find(&self, sha256_hash: &Sha256, tlsh: Option<&Tlsh>) -> Option<AllowlistMatch> {
+        let exact = self.entries.iter().enumerate().find_map(|(index, entry)| {
+            matches!(entry, AllowlistEntry::Sha256(hash) if hash == sha256_hash).then_some(
+                AllowlistMatch {
+                    index,
+                    entry: *entry,
+                    distance: None,
+                },
+            )
+        });
+        if exact.is_some() {
+            return exact;
+        }
+
+        let tlsh = tlsh?;
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| match entry {
+                AllowlistEntry::Tlsh {
+                    tlsh: entry_tlsh,
+                    max_distance,
+                } => {
+                    let distance = tlsh.distance(&entry_tlsh.to_tlsh());
+                    (distance <= *max_distance as u32).then_some(AllowlistMatch {
+                        index,
+                        entry: *entry,
+                        distance: Some(distance),
+                    })
+                }
+                AllowlistEntry::Sha256(_) => None,
+            })
+            .min_by_key(|m| (m.distance, m.index))
+    }
+}
+
+impl ObjectImpl for AllowlistObject {
+    const NAME: &'static str = "Allowlist";
+
+    /// SHA256 entries are stored as a zero byte and the hash, TLSH entries as a one byte, the
+    /// maximum distance (u16) and the digest.
+    fn to_object(self) -> Object {
+        Object {
+            format: 0x000C,
+            compression_type: ObjectCompressionType::NoCompression,
+            entry_type: 0,
+            entry_size: VARIABLE_ENTRY_SIZE,
+            data: self
+                .entries
+                .into_iter()
+                .map(|e| match e {
+                    AllowlistEntry::Sha256(hash) => {
+                        let mut e_vec = vec![0];
+                        e_vec.extend_from_slice(hash.as_bytes());
+                        e_vec
+                    }
+                    AllowlistEntry::Tlsh { tlsh, max_distance } => {
+                        let mut e_vec = vec![1];
+                        e_vec.extend_from_slice(&max_distance.to_be_bytes());
+                        e_vec.extend_from_slice(tlsh.as_bytes());
+                        e_vec
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    fn from_object(obj: Object) -> Option<Self> {
+        Self::try_from_object(&obj).ok()
+    }
+}
+
+impl ObjectFormat for AllowlistObject {
+    const FORMAT: u16 = 0x000C;
+    const ENTRY_TYPES: &'static [u16] = &[0];
+
+    fn try_from_object(obj: &Object) -> Result<Self, FormatError> {
+        Self::check_object(obj)?;
+        check_entry_size(obj, VARIABLE_ENTRY_SIZE)?;
+
+        let mut entries = Vec::new();
+        for (index, entry) in obj.data.iter().enumerate() {
+            let e = match (entry.first(), entry.len()) {
+                (Some(0), 33) => AllowlistEntry::Sha256(Sha256::from_bytes(&entry[1..33]).unwrap()),
+                (Some(1), 38) => AllowlistEntry::Tlsh {
+                    tlsh: TlshDigest::from_bytes(&entry[3..38]).unwrap(),
+                    max_distance: u16::from_be_bytes(entry[1..3].try_into().unwrap()),
+                },
+                _ => return Err(FormatError::MalformedEntry(index)),
+            };
+            entries.push(e);
+        }
+
+        Ok(Self { entries })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::database::{FormatError, ObjectFormat, ObjectImpl};
+    use crate::formats::allowlist::{AllowlistEntry, AllowlistObject};
+    use crate::hash::Sha256;
+    use crate::tlsh::{test_digest, Tlsh, TLSH_LENGTH};
+
+    #[test]
+    pub fn test_allowlist() {
+        let mut allowlist = AllowlistObject::empty();
+        allowlist.add_tlsh(test_digest::<TLSH_LENGTH>(3), 10);
+        allowlist.add_tlsh(test_digest::<TLSH_LENGTH>(1), 1);
+        allowlist.add_sha256([7; 32]);
+        allowlist.add_tlsh(test_digest::<TLSH_LENGTH>(2), 0);
+
+        let allowlist = AllowlistObject::from_object(allowlist.to_object()).unwrap();
+        assert_eq!(
+            allowlist.get_entries()[2],
+            AllowlistEntry::Sha256(Sha256::from([7; 32]))
+        );
+
+        let query = Tlsh::from_bytes(&[0u8; 35]).unwrap();
+        let found = allowlist
+            .find(&Sha256::from([7; 32]), Some(&query))
+            .unwrap();
+        assert_eq!((found.index, found.distance), (2, None));
+
+        // Entry 3 is too far, entry 1 is the closest of the others
+        let found = allowlist.find(&Sha256::default(), Some(&query)).unwrap();
+        assert_eq!((found.index, found.distance), (1, Some(1)));
+
+        assert!(allowlist.find(&Sha256::default(), None).is_none());
+        let far = Tlsh::from_bytes(&test_digest::<TLSH_LENGTH>(0xff)).unwrap();
+        assert!(allowlist.find(&Sha256::default(), Some(&far)).is_none());
+
+        let mut invalid = allowlist.to_object();
+        invalid.data[0].pop();
+        assert_eq!(
+            AllowlistObject::try_from_object(&invalid).err(),
+            Some(FormatError::MalformedEntry(0))
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use crate::database::{
+    check_entry_size, FormatError, Object, ObjectCompressionType, ObjectFormat, ObjectImpl,
+};
+use crate::formats::colored_tlsh::ColoredTLSHObject;
+use crate::formats::colored_tlsh_with_distance::ColoredTLSHWithDistanceObject;
+use crate::formats::sha256_set::SHA256SetObject;
+use crate::formats::simple_tlsh::SimpleTLSHObject;
+use crate::formats::telfhash::TelfhashObject;
+use crate::tlsh::TLSH_LENGTH;
+use std::f64::consts::LN_2;
+
+/// Largest supported number of hash functions. Filters built for any false positive rate use
+/// fewer, larger values are rejected when decoding.
+pub const MAX_HASH_COUNT: u32 = 64;
+
+/// Key field of an object to build a filter from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyField {
+    /// SHA256 hashes of formats 0x0002, 0x0003, 0x0005 and 0x0008.
+    Sha256,
+    /// Standard 35-byte binary TLSH digests of formats 0x0001 (hex digests are converted),
+    /// 0x0002, 0x0003 (without the colour byte) and 0x0008. Query the filter with the first 35
+    /// bytes of colored digests too.
+    Tlsh,
+}
+
+/// Object format 0x0006, BloomFilter.
+///
+/// Probabilistic set membership filter. A negative answer is always correct, a positive answer
+/// is wrong with the false positive rate the filter was built for. Can be built from the keys of
+/// another object, so the full object only needs to be loaded if the filter reports a match.
+///
+/// The keys are hashed with 64-bit FNV-1a and SplitMix64, so the filter gives the same answers on
+/// every platform.
+pub struct BloomFilterObject {
+    /// Target false positive rate in parts per million.
+    false_positive_ppm: u32,
+    hash_count: u32,
+    key_count: u64,
+    bits: Vec<u64>,
+}
+
+impl BloomFilterObject {
+    /// New, empty filter sized for `expected_keys` keys at the provided false positive rate.
+    ///
+    /// Panics if the rate is not between 0 and 1.
+    pub fn new(expected_keys: usize, false_positive_rate: f64) -> Self {
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "invalid false positive rate"
+        );
+        let expected_keys = expected_keys.max(1) as f64;
+        let bit_count = (-expected_keys * false_positive_rate.ln() / (LN_2 * LN_2)).ceil();
+        let words = ((bit_count / 64.0).ceil() as usize).max(1);
+        let hash_count = ((words * 64) as f64 / expected_keys * LN_2)
+            .round()
+            .clamp(1.0, MAX_HASH_COUNT as f64);
+
+        Self {
+            false_positive_ppm: ((false_positive_rate * 1_000_000.0).round() as u32).max(1),
+            hash_count: hash_count as u32,
+            key_count: 0,
+            bits: vec![0; words],
+        }
+    }
+
+    /// Build a filter from a list of keys.
+    pub fn from_keys<'a, I: IntoIterator<Item = &'a [u8]>>(
+        keys: I,
+        false_positive_rate: f64,
+    ) -> Self {
+        let keys: Vec<&[u8]> = keys.into_iter().collect();
+        let mut filter = Self::new(keys.len(), false_positive_rate);
+        keys.into_iter().for_each(|k| filter.insert(k));
+        filter
+    }
+
+    /// Build a filter from a key field of an object.
+    ///
+    /// Returns none if the object format does not store the requested key, or the object is
+    /// invalid.
+    pub fn from_object_keys(
+        obj: &Object,
+        field: KeyField,
+        false_positive_rate: f64,
+    ) -> Option<Self> {
+        let tlsh_key = |bytes: &[u8]| bytes[..TLSH_LENGTH].to_vec();
+        let keys: Vec<Vec<u8>> = match (obj.format, field) {
+            (0x0001, KeyField::Tlsh) => SimpleTLSHObject::try_from_object(obj)
+                .ok()?
+                .get_raw_hashes(),
+            (0x0002, _) => {
+                let object = ColoredTLSHObject::try_from_object(obj).ok()?;
+                let entries = object.get_entries().iter();
+                match field {
+                    KeyField::Tlsh => entries.map(|e| tlsh_key(e.tlsh.as_bytes())).collect(),
+                    KeyField::Sha256 => {
+                        entries.map(|e| e.sha256_hash.as_bytes().to_vec()).collect()
+                    }
+                }
+            }
+            (0x0003, _) => {
+                let object = ColoredTLSHWithDistanceObject::try_from_object(obj).ok()?;
+                let entries = object.get_entries().iter();
+                match field {
+                    KeyField::Tlsh => entries.map(|e| tlsh_key(e.tlsh.as_bytes())).collect(),
+                    KeyField::Sha256 => {
+                        entries.map(|e| e.sha256_hash.as_bytes().to_vec()).collect()
+                    }
+                }
+            }
+            (0x0005, KeyField::Sha256) => SHA256SetObject::try_from_object(obj)
+                .ok()?
+                .get_hashes()
+                .iter()
+                .map(|h| h.as_bytes().to_vec())
+                .collect(),
+            (0x0008, _) => {
+                let object = TelfhashObject::try_from_object(obj).ok()?;
+                let entries = object.get_entries().iter();
+                match field {
+                    KeyField::Tlsh => entries.map(|e| tlsh_key(e.telfhash.as_bytes())).collect(),
+                    KeyField::Sha256 => {
+                        entries.map(|e| e.sha256_hash.as_bytes().to_vec()).collect()
+                    }
+                }
+            }
+            _ => return None,
+        };
+        Some(Self::from_keys(
+            keys.iter().map(|k| k.as_slice()),
+            false_positive_rate,
+        ))
+    }
+
+    /// Add a key to the filter.
+    pub fn insert(&mut self, key: &[u8]) {
+        let bit_count = self.bits.len() as u64 * 64;
+        for bit in bit_indices(key, self.hash_count, bit_count) {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+        self.key_count += 1;
+    }
+
+    /// Check if the key may be in the filter. False means the key is definitely not present.
+    pub fn contains(&self, key: &[u8]) -> bool {
+        let bit_count = self.bits.len() as u64 * 64;
+        bit_indices(key, self.hash_count, bit_count)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    /// The false positive rate the filter was built for.
+    pub fn false_positive_rate(&self) -> f64 {
+        self.false_positive_ppm as f64 / 1_000_000.0
+    }
+
+    /// Number of inserted keys.
+    pub fn key_count(&self) -> u64 {
+        self.key_count
+    }
+}
+
+impl ObjectImpl for BloomFilterObject {
+    const NAME: &'static str = "BloomFilter";
+
+    fn to_object(self) -> Object {
+        let mut parameters = self.false_positive_ppm.to_be_bytes().to_vec();
+        parameters.extend_from_slice(&self.hash_count.to_be_bytes());
+
+        let mut data = vec![parameters, self.key_count.to_be_bytes().to_vec()];
+        data.extend(self.bits.iter().map(|w| w.to_be_bytes().to_vec()));
+
+        Object {
+            format: 0x0006,
+            compression_type: ObjectCompressionType::NoCompression,
+            entry_type: 0,
+            entry_size: 8,
+            data,
+        }
+    }
+
+    fn from_object(obj: Object) -> Option<Self> {
+        Self::try_from_object(&obj).ok()
+    }
+}
+
+impl ObjectFormat for BloomFilterObject {
+    const FORMAT: u16 = 0x0006;
+    const ENTRY_TYPES: &'static [u16] = &[0];
+
+    /// The number of hash functions must be between 1 and [`MAX_HASH_COUNT`].
+    fn try_from_object(obj: &Object) -> Result<Self, FormatError> {
+        Self::check_object(obj)?;
+        check_entry_size(obj, 8)?;
+        // The parameters, the key count and at least one word of bits
+        if obj.data.len() < 3 {
+            return Err(FormatError::MalformedEntry(obj.data.len()));
+        }
+
+        let hash_count = u32::from_be_bytes(obj.data[0][4..8].try_into().unwrap());
+        if hash_count == 0 || hash_count > MAX_HASH_COUNT {
+            return Err(FormatError::MalformedEntry(0));
+        }
+        Ok(Self {
+            false_positive_ppm: u32::from_be_bytes(obj.data[0][0..4].try_into().unwrap()),
+            hash_count,
+            key_count: u64::from_be_bytes(obj.data[1].as_slice().try_into().unwrap()),
+            bits: obj.data[2..]
+                .iter()
+                .map(|w| u64::from_be_bytes(w.as_slice().try_into().unwrap()))
+                .collect(),
+        })
+    }
+}
+
+/// Bit positions of a key, using double hashing.
+fn bit_indices(key: &[u8], hash_count: u32, bit_count: u64) -> impl Iterator<Item = u64> {
+    let h1 = fnv1a_64(key);
+    let h2 = splitmix64(h1) | 1;
+    (0..hash_count as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % bit_count)
+}
+
+fn fnv1a_64(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::database::{FormatError, ObjectFormat, ObjectImpl};
+    use crate::formats::bloom_filter::{BloomFilterObject, KeyField, MAX_HASH_COUNT};
+    use crate::formats::colored_tlsh::ColoredTLSHObject;
+    use crate::formats::sha256_set::SHA256SetObject;
+
+    fn key(i: u32) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        key[0..4].copy_from_slice(&i.to_be_bytes());
+        key
+    }
+
+    #[test]
+    pub fn test_false_positive_rate() {
+        let keys: Vec<[u8; 32]> = (0..2000).map(key).collect();
+        let filter = BloomFilterObject::from_keys(keys.iter().map(|k| k.as_slice()), 0.01);
+        assert!(keys.iter().all(|k| filter.contains(k)));
+
+        let false_positives = (2000..22000).filter(|i| filter.contains(&key(*i))).count();
+        assert!(false_positives < 20000 * 2 / 100, "{}", false_positives);
+
+        let filter = BloomFilterObject::from_object(filter.to_object()).unwrap();
+        assert_eq!(filter.false_positive_rate(), 0.01);
+        assert_eq!(filter.key_count(), 2000);
+        assert!(keys.iter().all(|k| filter.contains(k)));
+
+        let mut hostile = BloomFilterObject::new(1, 0.5).to_object();
+        hostile.data[0][4..8].copy_from_slice(&(MAX_HASH_COUNT + 1).to_be_bytes());
+        assert_eq!(
+            BloomFilterObject::try_from_object(&hostile).err(),
+            Some(FormatError::MalformedEntry(0))
+        );
+        assert!(BloomFilterObject::new(1, 0.999).hash_count <= MAX_HASH_COUNT);
+    }
+
+    #[test]
+    pub fn test_object_keys() {
+        let mut colored = ColoredTLSHObject::empty();
+        colored.add_entry([1; 36], [2; 32]);
+        let colored = colored.to_object();
+
+        let filter =
+            BloomFilterObject::from_object_keys(&colored, KeyField::Sha256, 0.001).unwrap();
+        assert!(filter.contains(&[2; 32]));
+        let filter = BloomFilterObject::from_object_keys(&colored, KeyField::Tlsh, 0.001).unwrap();
+        // Keyed on the standard digest, without the colour byte
+        assert!(filter.contains(&[1; 35]));
+        assert!(!filter.contains(&[1; 36]));
+
+        let set = SHA256SetObject::from_hashes([[3; 32]]).to_object();
+        assert!(BloomFilterObject::from_object_keys(&set, KeyField::Tlsh, 0.001).is_none());
+
+        let mut short = colored;
+        short.data[0].truncate(40);
+        assert!(BloomFilterObject::from_object_keys(&short, KeyField::Tlsh, 0.001).is_none());
+    }
+}
@@ -1,14 +1,36 @@
-use crate::database::{Object, ObjectCompressionType, ObjectImpl};
+use crate::database::{
+    check_entry_size, FormatError, Object, ObjectCompressionType, ObjectFormat, ObjectImpl,
+};
+use crate::hash::{Sha256, TlshDigest};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ColoredTLSHEntry {
-    pub tlsh_bytes: [u8; 36],
-    pub sha256_hash: [u8; 32],
+    /// The 36-byte colored digest.
+    pub tlsh: TlshDigest,
+    pub sha256_hash: Sha256,
+    pub label: Option<FamilyLabel>,
 }
 
+/// Family label of an entry, the family name in a string table object of the same database.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FamilyLabel {
+    /// ID of the string table object.
+    pub string_table_id: u64,
+    /// Index of the family name in the string table, see
+    /// [`Database::get_string`](crate::Database::get_string).
+    pub index: u32,
+}
+
+/// Stored label index of the entries without a label in the labelled entry type.
+pub(crate) const NO_LABEL: u32 = u32::MAX;
+
 /// Object format 0x0002, ColoredTLSH.
 ///
 /// Stores a list of the standard 35-byte TLSH hashes int binary format.
+///
+/// Entry type 0 stores the digest and the SHA256 hash, entry type 1 also stores a family label,
+/// the ID of a string table object and the index of the family name in it. Type 1 is used when
+/// saving if any of the entries has a label.
 pub struct ColoredTLSHObject {
     entries: Vec<ColoredTLSHEntry>,
 }
@@ -22,29 +44,89 @@ impl ColoredTLSHObject {
         &self.entries
     }
 
-    pub fn add_entry(&mut self, tlsh_hash: &[u8], sha_hash: &[u8]) {
+    /// Add an entry without a label.
+    ///
+    /// Panics if the digest is not colored.
+    pub fn add_entry(&mut self, tlsh_hash: impl Into<TlshDigest>, sha_hash: impl Into<Sha256>) {
+        self.push(tlsh_hash.into(), sha_hash.into(), None);
+    }
+
+    /// Add an entry with its family label.
+    ///
+    /// Panics if the digest is not colored or the label index is `u32::MAX`, which is reserved
+    /// for entries without a label.
+    pub fn add_labelled_entry(
+        &mut self,
+        tlsh_hash: impl Into<TlshDigest>,
+        sha_hash: impl Into<Sha256>,
+        label: FamilyLabel,
+    ) {
+        assert_ne!(label.index, NO_LABEL, "reserved label index");
+        self.push(tlsh_hash.into(), sha_hash.into(), Some(label));
+    }
+
+    fn push(&mut self, tlsh: TlshDigest, sha256_hash: Sha256, label: Option<FamilyLabel>) {
+        assert!(tlsh.is_colored(), "tlsh hash is not colored");
         self.entries.push(ColoredTLSHEntry {
-            tlsh_bytes: tlsh_hash.try_into().unwrap(),
-            sha256_hash: sha_hash.try_into().unwrap(),
+            tlsh,
+            sha256_hash,
+            label,
         });
     }
 }
 
+/// Entry size of the entry types, or none if the entry type is unknown.
+pub(crate) fn entry_size(entry_type: u16, unlabelled_size: u16) -> Option<u16> {
+    match entry_type {
+        0 => Some(unlabelled_size),
+        1 => Some(unlabelled_size + 8 + 4),
+        _ => None,
+    }
+}
+
+/// Append the label of an entry of the labelled entry type.
+pub(crate) fn write_label(entry: &mut Vec<u8>, label: Option<FamilyLabel>) {
+    let (string_table_id, index) = label.map_or((0, NO_LABEL), |l| (l.string_table_id, l.index));
+    entry.extend_from_slice(&string_table_id.to_be_bytes());
+    entry.extend_from_slice(&index.to_be_bytes());
+}
+
+/// Parse the label at the end of an entry of the labelled entry type.
+pub(crate) fn parse_label(entry_type: u16, entry: &[u8]) -> Option<FamilyLabel> {
+    if entry_type != 1 {
+        return None;
+    }
+    let label = &entry[entry.len() - 12..];
+    let index = u32::from_be_bytes(label[8..12].try_into().unwrap());
+    (index != NO_LABEL).then_some(FamilyLabel {
+        string_table_id: u64::from_be_bytes(label[0..8].try_into().unwrap()),
+        index,
+    })
+}
+
 impl ObjectImpl for ColoredTLSHObject {
     const NAME: &'static str = "ColoredTLSH";
 
     fn to_object(self) -> Object {
+        let entry_type = if self.entries.iter().any(|e| e.label.is_some()) {
+            1
+        } else {
+            0
+        };
         Object {
             format: 0x0002,
             compression_type: ObjectCompressionType::NoCompression,
-            entry_type: 0,
-            entry_size: 36 + 32,
+            entry_type,
+            entry_size: entry_size(entry_type, 36 + 32).unwrap(),
             data: self
                 .entries
                 .into_iter()
                 .map(|e| {
-                    let mut e_vec = e.tlsh_bytes.to_vec();
-                    e.sha256_hash.into_iter().for_each(|e| e_vec.push(e));
+                    let mut e_vec = e.tlsh.as_bytes().to_vec();
+                    e_vec.extend_from_slice(e.sha256_hash.as_bytes());
+                    if entry_type == 1 {
+                        write_label(&mut e_vec, e.label);
+                    }
                     e_vec
                 })
                 .collect(),
@@ -55,19 +137,82 @@ impl ObjectImpl for ColoredTLSHObject {
     where
         Self: Sized,
     {
-        if obj.format != 0x0002 {
-            return None;
-        }
+        Self::try_from_object(&obj).ok()
+    }
+}
+
+impl ObjectFormat for ColoredTLSHObject {
+    const FORMAT: u16 = 0x0002;
+    const ENTRY_TYPES: &'static [u16] = &[0, 1];
+
+    fn try_from_object(obj: &Object) -> Result<Self, FormatError> {
+        Self::check_object(obj)?;
+        check_entry_size(obj, entry_size(obj.entry_type, 36 + 32).unwrap())?;
 
         let mut entries = Vec::new();
-        for entry in obj.data {
+        for entry in &obj.data {
             let e = ColoredTLSHEntry {
-                tlsh_bytes: entry[0..36].try_into().unwrap(),
-                sha256_hash: entry[36..36 + 32].try_into().unwrap(),
+                tlsh: TlshDigest::from_bytes(&entry[0..36]).unwrap(),
+                sha256_hash: Sha256::from_bytes(&entry[36..36 + 32]).unwrap(),
+                label: parse_label(obj.entry_type, entry),
             };
             entries.push(e);
         }
 
-        Some(Self { entries })
+        Ok(Self { entries })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::database::{FormatError, ObjectFormat, ObjectImpl};
+    use crate::formats::colored_tlsh::{ColoredTLSHObject, FamilyLabel};
+
+    #[test]
+    pub fn test_entry_types() {
+        let mut object = ColoredTLSHObject::empty();
+        object.add_entry([1; 36], [1; 32]);
+        let object = object.to_object();
+        assert_eq!((object.entry_type, object.entry_size), (0, 68));
+        let object = ColoredTLSHObject::from_object(object).unwrap();
+        assert_eq!(object.get_entries()[0].label, None);
+
+        let mut object = ColoredTLSHObject::empty();
+        object.add_entry([1; 36], [1; 32]);
+        let label = FamilyLabel {
+            string_table_id: 9,
+            index: 7,
+        };
+        object.add_labelled_entry([2; 36], [2; 32], label);
+        let object = object.to_object();
+        assert_eq!((object.entry_type, object.entry_size), (1, 80));
+        assert_eq!(
+            &object.data[0][68..],
+            &[0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(&object.data[1][68..76], &9u64.to_be_bytes());
+        let labels: Vec<Option<FamilyLabel>> = ColoredTLSHObject::try_from_object(&object)
+            .unwrap()
+            .get_entries()
+            .iter()
+            .map(|e| e.label)
+            .collect();
+        assert_eq!(labels, vec![None, Some(label)]);
+
+        let mut wrong_size = object.clone();
+        wrong_size.entry_size = 68;
+        assert_eq!(
+            ColoredTLSHObject::try_from_object(&wrong_size).err(),
+            Some(FormatError::InvalidEntrySize {
+                expected: 80,
+                found: 68
+            })
+        );
+        let mut unknown_type = object;
+        unknown_type.entry_type = 2;
+        assert_eq!(
+            ColoredTLSHObject::try_from_object(&unknown_type).err(),
+            Some(FormatError::UnsupportedEntryType(2))
+        );
     }
 }
@@ -1,15 +1,25 @@
-use crate::database::{Object, ObjectCompressionType, ObjectImpl};
+use crate::database::{
+    check_entry_size, FormatError, Object, ObjectCompressionType, ObjectFormat, ObjectImpl,
+};
+use crate::formats::colored_tlsh::{entry_size, parse_label, write_label, FamilyLabel, NO_LABEL};
+use crate::hash::{Sha256, TlshDigest};
+use crate::tlsh::Tlsh;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ColoredTLSHWithDistanceEntry {
-    pub tlsh_bytes: [u8; 36],
-    pub sha256_hash: [u8; 32],
+    /// The 36-byte colored digest.
+    pub tlsh: TlshDigest,
+    pub sha256_hash: Sha256,
     pub distance: u8,
+    pub label: Option<FamilyLabel>,
 }
 
 /// Object format 0x0003, ColoredTLSHWithDistance.
 ///
 /// Stores a list of the standard 35-byte TLSH hashes int binary format with a SHA256 hash and a detection distance.
+///
+/// Like [`ColoredTLSHObject`](crate::formats::colored_tlsh::ColoredTLSHObject), entry type 1
+/// also stores a family label for each entry.
 pub struct ColoredTLSHWithDistanceObject {
     entries: Vec<ColoredTLSHWithDistanceEntry>,
 }
@@ -23,31 +33,120 @@ impl ColoredTLSHWithDistanceObject {
         &self.entries
     }
 
-    pub fn add_entry(&mut self, tlsh_hash: &[u8], sha_hash: &[u8], distance: u8) {
+    /// Add an entry without a label.
+    ///
+    /// Panics if the digest is not colored.
+    pub fn add_entry(
+        &mut self,
+        tlsh_hash: impl Into<TlshDigest>,
+        sha_hash: impl Into<Sha256>,
+        distance: u8,
+    ) {
+        self.push(tlsh_hash.into(), sha_hash.into(), distance, None);
+    }
+
+    /// Add an entry with its family label.
+    ///
+    /// Panics if the digest is not colored or the label index is `u32::MAX`, which is reserved
+    /// for entries without a label.
+    pub fn add_labelled_entry(
+        &mut self,
+        tlsh_hash: impl Into<TlshDigest>,
+        sha_hash: impl Into<Sha256>,
+        distance: u8,
+        label: FamilyLabel,
+    ) {
+        assert_ne!(label.index, NO_LABEL, "reserved label index");
+        self.push(tlsh_hash.into(), sha_hash.into(), distance, Some(label));
+    }
+
+    fn push(
+        &mut self,
+        tlsh: TlshDigest,
+        sha256_hash: Sha256,
+        distance: u8,
+        label: Option<FamilyLabel>,
+    ) {
+        assert!(tlsh.is_colored(), "tlsh hash is not colored");
         self.entries.push(ColoredTLSHWithDistanceEntry {
-            tlsh_bytes: tlsh_hash.try_into().unwrap(),
-            sha256_hash: sha_hash.try_into().unwrap(),
+            tlsh,
+            sha256_hash,
             distance,
+            label,
         });
     }
 }
 
+/// An entry matched by [`ColoredTLSHWithDistanceObject::matches`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DistanceMatch {
+    /// Index of the matching entry.
+    pub index: usize,
+    pub sha256_hash: Sha256,
+    /// TLSH distance of the query from the entry.
+    pub distance: u32,
+    /// The threshold the distance was compared to, either the entry's own or the override.
+    pub threshold: u32,
+}
+
+impl DistanceMatch {
+    /// How far the distance is below the threshold. Larger is a stronger match.
+    pub fn margin(&self) -> u32 {
+        self.threshold - self.distance
+    }
+}
+
+impl ColoredTLSHWithDistanceObject {
+    /// Match a digest against every entry.
+    ///
+    /// An entry matches if the TLSH distance of the digest is not larger than the entry's
+    /// `distance` threshold, or `threshold_override` if provided. The matches are ordered by
+    /// margin, the strongest match first.
+    pub fn matches(&self, digest: &Tlsh, threshold_override: Option<u32>) -> Vec<DistanceMatch> {
+        let mut matches: Vec<DistanceMatch> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                let distance = digest.distance(&entry.tlsh.to_tlsh());
+                let threshold = threshold_override.unwrap_or(entry.distance as u32);
+                (distance <= threshold).then_some(DistanceMatch {
+                    index,
+                    sha256_hash: entry.sha256_hash,
+                    distance,
+                    threshold,
+                })
+            })
+            .collect();
+        matches.sort_by_key(|m| (std::cmp::Reverse(m.margin()), m.index));
+        matches
+    }
+}
+
 impl ObjectImpl for ColoredTLSHWithDistanceObject {
     const NAME: &'static str = "ColoredTLSHWithDistance";
 
     fn to_object(self) -> Object {
+        let entry_type = if self.entries.iter().any(|e| e.label.is_some()) {
+            1
+        } else {
+            0
+        };
         Object {
             format: 0x0003,
             compression_type: ObjectCompressionType::NoCompression,
-            entry_type: 0,
-            entry_size: 36 + 32 + 1,
+            entry_type,
+            entry_size: entry_size(entry_type, 36 + 32 + 1).unwrap(),
             data: self
                 .entries
                 .into_iter()
                 .map(|e| {
-                    let mut e_vec = e.tlsh_bytes.to_vec();
-                    e.sha256_hash.into_iter().for_each(|e| e_vec.push(e));
+                    let mut e_vec = e.tlsh.as_bytes().to_vec();
+                    e_vec.extend_from_slice(e.sha256_hash.as_bytes());
                     e_vec.push(e.distance);
+                    if entry_type == 1 {
+                        write_label(&mut e_vec, e.label);
+                    }
                     e_vec
                 })
                 .collect(),
@@ -58,20 +157,95 @@ impl ObjectImpl for ColoredTLSHWithDistanceObject {
         where
             Self: Sized,
     {
-        if obj.format != 0x0003 {
-            return None;
-        }
+        Self::try_from_object(&obj).ok()
+    }
+}
+
+impl ObjectFormat for ColoredTLSHWithDistanceObject {
+    const FORMAT: u16 = 0x0003;
+    const ENTRY_TYPES: &'static [u16] = &[0, 1];
+
+    fn try_from_object(obj: &Object) -> Result<Self, FormatError> {
+        Self::check_object(obj)?;
+        check_entry_size(obj, entry_size(obj.entry_type, 36 + 32 + 1).unwrap())?;
 
         let mut entries = Vec::new();
-        for entry in obj.data {
+        for entry in &obj.data {
             let e = ColoredTLSHWithDistanceEntry {
-                tlsh_bytes: entry[0..36].try_into().unwrap(),
-                sha256_hash: entry[36..36 + 32].try_into().unwrap(),
+                tlsh: TlshDigest::from_bytes(&entry[0..36]).unwrap(),
+                sha256_hash: Sha256::from_bytes(&entry[36..36 + 32]).unwrap(),
                 distance: entry[36 + 32],
+                label: parse_label(obj.entry_type, entry),
             };
             entries.push(e);
         }
 
-        Some(Self { entries })
+        Ok(Self { entries })
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use crate::database::{FormatError, ObjectFormat, ObjectImpl};
+    use crate::formats::colored_tlsh::FamilyLabel;
+    use crate::formats::colored_tlsh_with_distance::ColoredTLSHWithDistanceObject;
+    use crate::hash::Sha256;
+    use crate::tlsh::{test_digest, Tlsh, COLORED_TLSH_LENGTH};
+
+    #[test]
+    pub fn test_matches() {
+        let mut object = ColoredTLSHWithDistanceObject::empty();
+        object.add_entry(test_digest::<COLORED_TLSH_LENGTH>(1), [0; 32], 1);
+        object.add_entry(test_digest::<COLORED_TLSH_LENGTH>(2), [1; 32], 10);
+        object.add_entry(test_digest::<COLORED_TLSH_LENGTH>(3), [2; 32], 5);
+        object.add_entry(test_digest::<COLORED_TLSH_LENGTH>(0), [3; 32], 3);
+
+        let query = Tlsh::from_bytes(&[0u8; 36]).unwrap();
+        let matches = object.matches(&query, None);
+        let found: Vec<(usize, u32, u32)> = matches
+            .iter()
+            .map(|m| (m.index, m.distance, m.margin()))
+            .collect();
+        // Entry 2 is at distance 6, above its own threshold of 5
+        assert_eq!(found, vec![(1, 2, 8), (3, 0, 3), (0, 1, 0)]);
+        assert_eq!(matches[0].sha256_hash, Sha256::from([1; 32]));
+
+        let found: Vec<usize> = object
+            .matches(&query, Some(6))
+            .iter()
+            .map(|m| m.index)
+            .collect();
+        assert_eq!(found, vec![3, 0, 1, 2]);
+        assert_eq!(object.matches(&query, Some(0)).len(), 1);
+    }
+
+    #[test]
+    pub fn test_labelled_entries() {
+        let mut object = ColoredTLSHWithDistanceObject::empty();
+        let label = FamilyLabel {
+            string_table_id: 2,
+            index: 3,
+        };
+        object.add_labelled_entry(test_digest::<COLORED_TLSH_LENGTH>(1), [0; 32], 5, label);
+        object.add_entry(test_digest::<COLORED_TLSH_LENGTH>(2), [1; 32], 10);
+        let object = object.to_object();
+        assert_eq!((object.entry_type, object.entry_size), (1, 81));
+
+        let object = ColoredTLSHWithDistanceObject::from_object(object).unwrap();
+        let entries = object.get_entries();
+        assert_eq!((entries[0].distance, entries[0].label), (5, Some(label)));
+        assert_eq!((entries[1].distance, entries[1].label), (10, None));
+
+        let mut unlabelled = ColoredTLSHWithDistanceObject::empty();
+        unlabelled.add_entry(test_digest::<COLORED_TLSH_LENGTH>(1), [0; 32], 5);
+        let mut unlabelled = unlabelled.to_object();
+        assert_eq!((unlabelled.entry_type, unlabelled.entry_size), (0, 69));
+        unlabelled.entry_size = 81;
+        assert_eq!(
+            ColoredTLSHWithDistanceObject::try_from_object(&unlabelled).err(),
+            Some(FormatError::InvalidEntrySize {
+                expected: 69,
+                found: 81
+            })
+        );
+    }
+}
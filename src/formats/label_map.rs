@@ -0,0 +1,220 @@
+use crate::database::{
+    check_entry_size, FormatError, Object, ObjectCompressionType, ObjectFormat, ObjectImpl,
+    VARIABLE_ENTRY_SIZE,
+};
+use crate::hash::Sha256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleLabel {
+    pub sha256_hash: Sha256,
+    /// Index of the family name in the string table.
+    pub family: u32,
+    /// Indices of the tags in the string table.
+    pub tags: Vec<u32>,
+}
+
+/// A [`SampleLabel`] with the strings looked up from the string table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedLabel {
+    pub family: String,
+    pub tags: Vec<String>,
+}
+
+/// Object format 0x000A, LabelMap.
+///
+/// Maps sample SHA256 hashes to a family name and a list of tags. The names are stored in a
+/// [`StringTableObject`](crate::formats::string_table::StringTableObject) of the same database,
+/// the label map only stores the ID of the string table and the string indices. The labels are
+/// kept sorted by hash, so [`get`](Self::get) is a binary search.
+pub struct LabelMapObject {
+    string_table_id: u64,
+    labels: Vec<SampleLabel>,
+}
+
+impl LabelMapObject {
+    /// Create an empty label map using the string table with the specified object ID.
+    pub fn new(string_table_id: u64) -> Self {
+        Self {
+            string_table_id,
+            labels: vec![],
+        }
+    }
+
+    /// Object ID of the string table the indices refer to.
+    pub fn string_table_id(&self) -> u64 {
+        self.string_table_id
+    }
+
+    /// The stored labels, in ascending hash order.
+    pub fn get_labels(&self) -> &Vec<SampleLabel> {
+        &self.labels
+    }
+
+    /// Set the label of a sample, replacing its previous label.
+    pub fn add_label(&mut self, sha_hash: impl Into<Sha256>, family: u32, tags: &[u32]) {
+        let label = SampleLabel {
+            sha256_hash: sha_hash.into(),
+            family,
+            tags: tags.to_vec(),
+        };
+        match self
+            .labels
+            .binary_search_by_key(&label.sha256_hash, |l| l.sha256_hash)
+        {
+            Ok(position) => self.labels[position] = label,
+            Err(position) => self.labels.insert(position, label),
+        }
+    }
+
+    /// Get the label of a sample.
+    pub fn get(&self, sha_hash: &Sha256) -> Option<&SampleLabel> {
+        self.labels
+            .binary_search_by_key(sha_hash, |l| l.sha256_hash)
+            .ok()
+            .map(|position| &self.labels[position])
+    }
+}
+
+/// Find the label of a sample in a generic label map object without decoding the whole map.
+///
+/// Returns the string table ID and the label, or none if the object is not a label map or the
+/// sample has no label.
+pub(crate) fn lookup(obj: &Object, sha_hash: &Sha256) -> Option<(u64, SampleLabel)> {
+    if !is_label_map(obj) {
+        return None;
+    }
+    let string_table_id = parse_reference(obj.data.first()?)?;
+    let labels = &obj.data[1..];
+    let position = labels
+        .binary_search_by(|e| e.get(..32).cmp(&Some(sha_hash.as_ref())))
+        .ok()?;
+    Some((string_table_id, parse_label(&labels[position])?))
+}
+
+fn is_label_map(obj: &Object) -> bool {
+    obj.format == 0x000A && obj.entry_type == 0 && obj.entry_size == VARIABLE_ENTRY_SIZE
+}
+
+fn parse_reference(entry: &[u8]) -> Option<u64> {
+    Some(u64::from_be_bytes(entry.try_into().ok()?))
+}
+
+fn parse_label(entry: &[u8]) -> Option<SampleLabel> {
+    if entry.len() < 36 || !(entry.len() - 36).is_multiple_of(4) {
+        return None;
+    }
+    Some(SampleLabel {
+        sha256_hash: Sha256::from_bytes(&entry[0..32]).unwrap(),
+        family: u32::from_be_bytes(entry[32..36].try_into().unwrap()),
+        tags: entry[36..]
+            .chunks_exact(4)
+            .map(|t| u32::from_be_bytes(t.try_into().unwrap()))
+            .collect(),
+    })
+}
+
+impl ObjectImpl for LabelMapObject {
+    const NAME: &'static str = "LabelMap";
+
+    /// The first entry is the string table ID, every other entry is a SHA256 hash followed by
+    /// the family index and the tag indices.
+    fn to_object(self) -> Object {
+        let mut data = vec![self.string_table_id.to_be_bytes().to_vec()];
+        data.extend(self.labels.into_iter().map(|l| {
+            let mut e_vec = l.sha256_hash.as_bytes().to_vec();
+            e_vec.extend_from_slice(&l.family.to_be_bytes());
+            l.tags
+                .iter()
+                .for_each(|t| e_vec.extend_from_slice(&t.to_be_bytes()));
+            e_vec
+        }));
+        Object {
+            format: 0x000A,
+            compression_type: ObjectCompressionType::NoCompression,
+            entry_type: 0,
+            entry_size: VARIABLE_ENTRY_SIZE,
+            data,
+        }
+    }
+
+    fn from_object(obj: Object) -> Option<Self> {
+        Self::try_from_object(&obj).ok()
+    }
+}
+
+impl ObjectFormat for LabelMapObject {
+    const FORMAT: u16 = 0x000A;
+    const ENTRY_TYPES: &'static [u16] = &[0];
+
+    /// The labels must be sorted by hash, without duplicates.
+    fn try_from_object(obj: &Object) -> Result<Self, FormatError> {
+        Self::check_object(obj)?;
+        check_entry_size(obj, VARIABLE_ENTRY_SIZE)?;
+
+        let string_table_id = obj
+            .data
+            .first()
+            .and_then(|e| parse_reference(e))
+            .ok_or(FormatError::MalformedEntry(0))?;
+        let labels = obj.data[1..]
+            .iter()
+            .enumerate()
+            .map(|(index, e)| parse_label(e).ok_or(FormatError::MalformedEntry(index + 1)))
+            .collect::<Result<Vec<SampleLabel>, FormatError>>()?;
+        if let Some(index) = labels
+            .windows(2)
+            .position(|w| w[0].sha256_hash >= w[1].sha256_hash)
+        {
+            return Err(FormatError::MalformedEntry(index + 2));
+        }
+
+        Ok(Self {
+            string_table_id,
+            labels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::database::{FormatError, ObjectFormat, ObjectImpl};
+    use crate::formats::label_map::{lookup, LabelMapObject};
+    use crate::hash::Sha256;
+
+    #[test]
+    pub fn test_label_map() {
+        let mut map = LabelMapObject::new(9);
+        map.add_label([3; 32], 0, &[1, 2]);
+        map.add_label([1; 32], 3, &[]);
+        map.add_label([3; 32], 4, &[2]);
+        assert_eq!(map.get_labels().len(), 2);
+        assert_eq!(map.get(&Sha256::from([3; 32])).unwrap().family, 4);
+        assert!(map.get(&Sha256::from([2; 32])).is_none());
+
+        let object = map.to_object();
+        assert_eq!(object.data[1].len(), 36);
+        assert_eq!(object.data[2].len(), 40);
+        let (string_table_id, label) = lookup(&object, &Sha256::from([3; 32])).unwrap();
+        assert_eq!(string_table_id, 9);
+        assert_eq!(label.tags, vec![2]);
+        assert!(lookup(&object, &Sha256::from([2; 32])).is_none());
+
+        let map = LabelMapObject::try_from_object(&object).unwrap();
+        assert_eq!(map.string_table_id(), 9);
+        assert_eq!(map.get(&Sha256::from([1; 32])).unwrap().family, 3);
+
+        let mut unsorted = object.clone();
+        unsorted.data.swap(1, 2);
+        assert_eq!(
+            LabelMapObject::try_from_object(&unsorted).err(),
+            Some(FormatError::MalformedEntry(2))
+        );
+
+        let mut truncated = object;
+        truncated.data[2].pop();
+        assert_eq!(
+            LabelMapObject::try_from_object(&truncated).err(),
+            Some(FormatError::MalformedEntry(2))
+        );
+    }
+}
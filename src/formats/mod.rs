@@ -1,5 +1,17 @@
 //! Built-in implementation of a number of supported formats.
 
+pub mod allowlist;
+pub mod bloom_filter;
 pub mod colored_tlsh;
 pub mod simple_tlsh;
-pub mod colored_tlsh_with_distance;
\ No newline at end of file
+pub mod colored_tlsh_with_distance;
+pub mod label_map;
+pub mod registry;
+pub mod sha256_set;
+pub mod ssdeep;
+pub mod string_table;
+pub mod telfhash;
+pub mod tlsh_index;
+pub mod yara_rules;
+
+pub use registry::{get_concrete_object, AnyObject};
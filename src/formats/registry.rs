@@ -0,0 +1,355 @@
+//! Mapping of format IDs to object implementations.
+//!
+//! [`get_concrete_object`] decodes a generic [`Object`] into the matching [`AnyObject`] variant,
+//! without the caller knowing the format in advance. Downstream crates can add their own
+//! formats with [`register_format`], these are decoded into [`AnyObject::Custom`]. The format
+//! IDs of the built-in formats cannot be used by custom formats.
+//!
+//! # Example: Register a custom format
+//! ```rust
+//! use simbiota_database::formats::registry::{get_concrete_object, register_format, AnyObject};
+//! use simbiota_database::{Object, ObjectCompressionType, ObjectImpl};
+//!
+//! /// A list of u32 values.
+//! struct MyFormat(Vec<u32>);
+//!
+//! impl ObjectImpl for MyFormat {
+//!     const NAME: &'static str = "MyFormat";
+//!     fn to_object(self) -> Object {
+//!         let data = self.0.iter().map(|v| v.to_be_bytes().to_vec()).collect();
+//!         Object::new(0x8001, ObjectCompressionType::NoCompression, 0, 4, data)
+//!     }
+//!     fn from_object(obj: Object) -> Option<Self> {
+//!         if obj.format() != 0x8001 || obj.entry_type() != 0 || obj.entry_size() != 4 {
+//!             return None;
+//!         }
+//!         let values = obj
+//!             .data()
+//!             .iter()
+//!             .map(|e| Some(u32::from_be_bytes(e.as_slice().try_into().ok()?)))
+//!             .collect::<Option<Vec<u32>>>()?;
+//!         Some(MyFormat(values))
+//!     }
+//! }
+//!
+//! register_format::<MyFormat>(0x8001, &[0]).expect("format already registered");
+//!
+//! let object = get_concrete_object(MyFormat(vec![1, 2]).to_object()).unwrap();
+//! assert_eq!(object.name(), "MyFormat");
+//! let AnyObject::Custom(custom) = object else {
+//!     panic!("not a custom object");
+//! };
+//! assert_eq!(custom.downcast_ref::<MyFormat>().unwrap().0, vec![1, 2]);
+//! ```
+
+use crate::database::{Object, ObjectFormat, ObjectImpl};
+use crate::formats::allowlist::AllowlistObject;
+use crate::formats::bloom_filter::BloomFilterObject;
+use crate::formats::colored_tlsh::ColoredTLSHObject;
+use crate::formats::colored_tlsh_with_distance::ColoredTLSHWithDistanceObject;
+use crate::formats::label_map::LabelMapObject;
+use crate::formats::sha256_set::SHA256SetObject;
+use crate::formats::simple_tlsh::SimpleTLSHObject;
+use crate::formats::ssdeep::SsdeepObject;
+use crate::formats::string_table::StringTableObject;
+use crate::formats::telfhash::TelfhashObject;
+use crate::formats::tlsh_index::TLSHIndexObject;
+use crate::formats::yara_rules::YaraRulesObject;
+use std::any::Any;
+use std::sync::RwLock;
+
+/// Declares the built-in formats: the [`AnyObject`] variants, [`BUILTIN_FORMATS`] with the
+/// decoder of each format, and the `TryFrom<&Object>` implementations. A new built-in format is
+/// only added to the invocation below.
+macro_rules! builtin_formats {
+    ($($variant:ident($object:ty)),* $(,)?) => {
+        /// Any decoded object, either one of the built-in formats or a registered custom format.
+        pub enum AnyObject {
+            $($variant($object),)*
+            /// A format registered with [`register_format`].
+            Custom(Box<dyn DynObject>),
+        }
+
+        impl AnyObject {
+            /// Name of the object format, [`ObjectImpl::NAME`] of the implementation.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(AnyObject::$variant(_) => <$object as ObjectImpl>::NAME,)*
+                    AnyObject::Custom(object) => object.name(),
+                }
+            }
+
+            /// Convert the object back to a generic object.
+            pub fn to_object(self) -> Object {
+                match self {
+                    $(AnyObject::$variant(object) => object.to_object(),)*
+                    AnyObject::Custom(object) => object.into_object(),
+                }
+            }
+        }
+
+        /// The formats implemented by this crate.
+        pub const BUILTIN_FORMATS: [FormatInfo; [$(stringify!($variant)),*].len()] =
+            [$(builtin::<$object>()),*];
+
+        /// Decoders of [`BUILTIN_FORMATS`], in the same order.
+        const BUILTIN_DECODERS: [Decoder; BUILTIN_FORMATS.len()] =
+            [$(|obj| <$object as ObjectImpl>::from_object(obj).map(AnyObject::$variant)),*];
+
+        crate::database::impl_try_from_object!($($object),*);
+    };
+}
+
+builtin_formats!(
+    SimpleTLSH(SimpleTLSHObject),
+    ColoredTLSH(ColoredTLSHObject),
+    ColoredTLSHWithDistance(ColoredTLSHWithDistanceObject),
+    TLSHIndex(TLSHIndexObject),
+    SHA256Set(SHA256SetObject),
+    BloomFilter(BloomFilterObject),
+    Ssdeep(SsdeepObject),
+    Telfhash(TelfhashObject),
+    StringTable(StringTableObject),
+    LabelMap(LabelMapObject),
+    YaraRules(YaraRulesObject),
+    Allowlist(AllowlistObject),
+);
+
+/// Object safe version of [`ObjectImpl`], implemented for every implementation.
+pub trait DynObject: Any + Send + Sync {
+    fn name(&self) -> &'static str;
+    fn into_object(self: Box<Self>) -> Object;
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: ObjectImpl + Send + Sync + 'static> DynObject for T {
+    fn name(&self) -> &'static str {
+        T::NAME
+    }
+
+    fn into_object(self: Box<Self>) -> Object {
+        (*self).to_object()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl dyn DynObject {
+    /// Get the concrete implementation of a custom object.
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+}
+
+/// A supported format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FormatInfo {
+    pub format: u16,
+    /// The entry types the implementation can decode.
+    pub entry_types: &'static [u16],
+    pub name: &'static str,
+}
+
+const fn builtin<T: ObjectFormat>() -> FormatInfo {
+    FormatInfo {
+        format: T::FORMAT,
+        entry_types: T::ENTRY_TYPES,
+        name: T::NAME,
+    }
+}
+
+/// Error representing failures that can occur while registering a format.
+#[derive(Debug, PartialEq)]
+pub enum RegistrationError {
+    /// A format is already registered for the format ID and entry type.
+    AlreadyRegistered { format: u16, entry_type: u16 },
+    /// The format ID belongs to a built-in format.
+    BuiltinFormat(u16),
+    /// No entry types were specified.
+    NoEntryTypes,
+}
+
+/// Decodes an object into the variant of its format, none if the object is invalid.
+type Decoder = fn(Object) -> Option<AnyObject>;
+
+struct CustomFormat {
+    info: FormatInfo,
+    decoder: Decoder,
+}
+
+static CUSTOM_FORMATS: RwLock<Vec<CustomFormat>> = RwLock::new(Vec::new());
+
+/// Register a custom format for the specified format ID and entry types. The objects are
+/// decoded with [`ObjectImpl::from_object`] of `T` into [`AnyObject::Custom`].
+///
+/// Fails if the format ID belongs to a built-in format, no entry types are specified, or any of
+/// the entry types is already registered for the format ID.
+pub fn register_format<T: ObjectImpl + Send + Sync + 'static>(
+    format: u16,
+    entry_types: &'static [u16],
+) -> Result<(), RegistrationError> {
+    if BUILTIN_FORMATS.iter().any(|info| info.format == format) {
+        return Err(RegistrationError::BuiltinFormat(format));
+    }
+    if entry_types.is_empty() {
+        return Err(RegistrationError::NoEntryTypes);
+    }
+    let mut custom_formats = CUSTOM_FORMATS.write().unwrap();
+    for info in custom_formats
+        .iter()
+        .map(|f| &f.info)
+        .filter(|info| info.format == format)
+    {
+        if let Some(entry_type) = entry_types.iter().find(|t| info.entry_types.contains(t)) {
+            return Err(RegistrationError::AlreadyRegistered {
+                format,
+                entry_type: *entry_type,
+            });
+        }
+    }
+
+    custom_formats.push(CustomFormat {
+        info: FormatInfo {
+            format,
+            entry_types,
+            name: T::NAME,
+        },
+        decoder: decode_custom::<T>,
+    });
+    Ok(())
+}
+
+fn decode_custom<T: ObjectImpl + Send + Sync + 'static>(obj: Object) -> Option<AnyObject> {
+    T::from_object(obj).map(|object| AnyObject::Custom(Box::new(object)))
+}
+
+/// Every built-in and registered format.
+pub fn registered_formats() -> Vec<FormatInfo> {
+    let custom_formats = CUSTOM_FORMATS.read().unwrap();
+    BUILTIN_FORMATS
+        .iter()
+        .copied()
+        .chain(custom_formats.iter().map(|f| f.info))
+        .collect()
+}
+
+/// Find the format implementing a format ID and entry type.
+pub fn find_format(format: u16, entry_type: u16) -> Option<FormatInfo> {
+    registered_formats()
+        .into_iter()
+        .find(|info| info.format == format && info.entry_types.contains(&entry_type))
+}
+
+/// Decode a generic object with the implementation registered for its format and entry type.
+///
+/// Returns none if the format and entry type are unknown or the object is invalid.
+pub fn get_concrete_object(obj: Object) -> Option<AnyObject> {
+    let implements =
+        |info: &FormatInfo| info.format == obj.format && info.entry_types.contains(&obj.entry_type);
+    let decoder = match BUILTIN_FORMATS.iter().position(implements) {
+        Some(index) => BUILTIN_DECODERS[index],
+        None => CUSTOM_FORMATS
+            .read()
+            .unwrap()
+            .iter()
+            .find(|f| implements(&f.info))
+            .map(|f| f.decoder)?,
+    };
+    decoder(obj)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::database::{Object, ObjectCompressionType, ObjectImpl};
+    use crate::formats::registry::{
+        find_format, get_concrete_object, register_format, AnyObject, RegistrationError,
+        BUILTIN_FORMATS,
+    };
+    use crate::formats::sha256_set::SHA256SetObject;
+    use crate::formats::string_table::StringTableObject;
+
+    struct Counter(u64);
+
+    impl ObjectImpl for Counter {
+        const NAME: &'static str = "Counter";
+
+        fn to_object(self) -> Object {
+            Object {
+                format: 0x7f00,
+                compression_type: ObjectCompressionType::NoCompression,
+                entry_type: 1,
+                entry_size: 8,
+                data: vec![self.0.to_be_bytes().to_vec()],
+            }
+        }
+
+        fn from_object(obj: Object) -> Option<Self> {
+            if obj.format != 0x7f00 || obj.entry_type != 1 || obj.data.len() != 1 {
+                return None;
+            }
+            Some(Counter(u64::from_be_bytes(
+                obj.data[0].as_slice().try_into().ok()?,
+            )))
+        }
+    }
+
+    #[test]
+    pub fn test_builtin_formats() {
+        let mut strings = StringTableObject::empty();
+        strings.intern("Mirai");
+        let object = get_concrete_object(strings.to_object()).unwrap();
+        assert_eq!(object.name(), "StringTable");
+        assert!(matches!(object, AnyObject::StringTable(ref s) if s.get(0) == Some("Mirai")));
+        assert_eq!(object.to_object().format, 0x0009);
+
+        let set = SHA256SetObject::empty().to_object();
+        assert!(matches!(
+            get_concrete_object(set.clone()),
+            Some(AnyObject::SHA256Set(_))
+        ));
+        // Built-in formats are selected by the entry type too
+        let mut unknown_entry_type = set;
+        unknown_entry_type.entry_type = 3;
+        assert!(get_concrete_object(unknown_entry_type).is_none());
+        assert_eq!(find_format(0x0001, 1).unwrap().name, "SimpleTLSH");
+        assert!(find_format(0x0001, 2).is_none());
+        assert!(BUILTIN_FORMATS
+            .windows(2)
+            .all(|w| w[0].format < w[1].format));
+    }
+
+    #[test]
+    pub fn test_custom_format() {
+        // Built-in format IDs are rejected, even with entry types the built-in format lacks
+        assert_eq!(
+            register_format::<Counter>(0x0001, &[5]),
+            Err(RegistrationError::BuiltinFormat(0x0001))
+        );
+        assert_eq!(
+            register_format::<Counter>(0x7f00, &[]),
+            Err(RegistrationError::NoEntryTypes)
+        );
+        assert!(get_concrete_object(Counter(7).to_object()).is_none());
+
+        register_format::<Counter>(0x7f00, &[1]).unwrap();
+        assert_eq!(
+            register_format::<Counter>(0x7f00, &[0, 1]),
+            Err(RegistrationError::AlreadyRegistered {
+                format: 0x7f00,
+                entry_type: 1
+            })
+        );
+        assert_eq!(find_format(0x7f00, 1).unwrap().name, "Counter");
+
+        let object = get_concrete_object(Counter(7).to_object()).unwrap();
+        assert_eq!(object.name(), "Counter");
+        let AnyObject::Custom(custom) = object else {
+            panic!("not a custom object");
+        };
+        assert_eq!(custom.downcast_ref::<Counter>().unwrap().0, 7);
+        assert!(custom.downcast_ref::<SHA256SetObject>().is_none());
+        assert_eq!(custom.into_object().data, vec![7u64.to_be_bytes().to_vec()]);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::database::{
+    check_entry_size, FormatError, Object, ObjectCompressionType, ObjectFormat, ObjectImpl,
+};
+use crate::hash::Sha256;
+
+/// Object format 0x0005, SHA256Set.
+///
+/// Stores a set of SHA256 hashes for exact-match lookups. The hashes are kept sorted and
+/// deduplicated, so [`contains`](Self::contains) is a binary search.
+pub struct SHA256SetObject {
+    hashes: Vec<Sha256>,
+}
+
+impl SHA256SetObject {
+    pub fn empty() -> Self {
+        Self { hashes: vec![] }
+    }
+
+    /// Create a set from any number of hashes, in any order.
+    pub fn from_hashes<I: IntoIterator<Item = T>, T: Into<Sha256>>(hashes: I) -> Self {
+        let mut hashes: Vec<Sha256> = hashes.into_iter().map(Into::into).collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        Self { hashes }
+    }
+
+    /// The stored hashes, in ascending order.
+    pub fn get_hashes(&self) -> &Vec<Sha256> {
+        &self.hashes
+    }
+
+    /// Add a hash to the set. Adding an already present hash has no effect.
+    pub fn add_hash(&mut self, sha_hash: impl Into<Sha256>) {
+        let hash = sha_hash.into();
+        if let Err(position) = self.hashes.binary_search(&hash) {
+            self.hashes.insert(position, hash);
+        }
+    }
+
+    /// Check if the set contains a hash.
+    pub fn contains(&self, sha_hash: &Sha256) -> bool {
+        self.hashes.binary_search(sha_hash).is_ok()
+    }
+}
+
+impl ObjectImpl for SHA256SetObject {
+    const NAME: &'static str = "SHA256Set";
+
+    fn to_object(self) -> Object {
+        Object {
+            format: 0x0005,
+            compression_type: ObjectCompressionType::NoCompression,
+            entry_type: 0,
+            entry_size: 32,
+            data: self.hashes.iter().map(|h| h.as_bytes().to_vec()).collect(),
+        }
+    }
+
+    fn from_object(obj: Object) -> Option<Self> {
+        Self::try_from_object(&obj).ok()
+    }
+}
+
+impl ObjectFormat for SHA256SetObject {
+    const FORMAT: u16 = 0x0005;
+    const ENTRY_TYPES: &'static [u16] = &[0];
+
+    /// The stored hashes must be strictly ascending.
+    fn try_from_object(obj: &Object) -> Result<Self, FormatError> {
+        Self::check_object(obj)?;
+        check_entry_size(obj, 32)?;
+
+        let hashes: Vec<Sha256> = obj
+            .data
+            .iter()
+            .map(|e| Sha256::from_bytes(e).unwrap())
+            .collect();
+        if let Some(index) = hashes.windows(2).position(|w| w[0] >= w[1]) {
+            return Err(FormatError::MalformedEntry(index + 1));
+        }
+
+        Ok(Self { hashes })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::database::{FormatError, ObjectFormat, ObjectImpl};
+    use crate::formats::sha256_set::SHA256SetObject;
+    use crate::hash::Sha256;
+
+    #[test]
+    pub fn test_sha256_set() {
+        let mut set = SHA256SetObject::from_hashes([[3; 32], [1; 32], [3; 32]]);
+        set.add_hash([2; 32]);
+        set.add_hash([1; 32]);
+        let expected: Vec<Sha256> = vec![[1; 32].into(), [2; 32].into(), [3; 32].into()];
+        assert_eq!(set.get_hashes(), &expected);
+        assert!(set.contains(&Sha256::from([2; 32])));
+        assert!(!set.contains(&Sha256::from([4; 32])));
+
+        let set = SHA256SetObject::from_object(set.to_object()).unwrap();
+        assert_eq!(set.get_hashes().len(), 3);
+        assert!(set.contains(&Sha256::from([3; 32])));
+
+        let mut unsorted = SHA256SetObject::empty().to_object();
+        unsorted.data = vec![vec![2; 32], vec![1; 32]];
+        assert_eq!(
+            SHA256SetObject::try_from_object(&unsorted).err(),
+            Some(FormatError::MalformedEntry(1))
+        );
+    }
+}
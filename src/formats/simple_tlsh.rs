@@ -1,8 +1,9 @@
 use crate::database::ObjectCompressionType::DEFLATE;
-use crate::database::{Object, ObjectCompressionType, ObjectImpl};
+use crate::database::{
+    check_entry_size, FormatError, Object, ObjectCompressionType, ObjectFormat, ObjectImpl,
+};
 use crate::formats::simple_tlsh::SimpleTLSHEntryType::{Hex, Raw};
-use std::fmt::Write;
-use std::num::ParseIntError;
+use crate::hash::TlshDigest;
 
 pub enum SimpleTLSHEntryType {
     Hex(String),
@@ -30,26 +31,27 @@ impl SimpleTLSHEntryType {
 /// Object format 0x0001, SimpleTLSH.
 ///
 /// Stores a list of the standard 35-byte TLSH hashes either in hexadecimal or in binary format.
+/// Hex hashes are stored in uppercase.
 pub struct SimpleTLSHObject {
-    entries: Vec<SimpleTLSHEntryType>,
+    entries: Vec<TlshDigest>,
     entry_type: SimpleTLSHEntryType,
     compressed: bool,
 }
 
 impl SimpleTLSHObject {
-    /// Get the stored hashes as hex Strings.
+    /// Get the stored hashes as hex Strings. Raw hashes are converted to uppercase hexadecimal.
     pub fn get_hashes(&self) -> Vec<String> {
-        if matches!(self.entry_type, Hex(_)) {
-            return self
-                .entries
-                .iter()
-                .map(|e| {
-                    let Hex(s) = e else { panic!("invalid entry") };
-                    s.clone()
-                })
-                .collect();
-        }
-        todo!();
+        self.entries.iter().map(|e| e.to_hex()).collect()
+    }
+
+    /// Get the stored hashes as 35-byte binary TLSH digests. Hex hashes are converted to binary.
+    pub fn get_raw_hashes(&self) -> Vec<Vec<u8>> {
+        self.entries.iter().map(|e| e.as_bytes().to_vec()).collect()
+    }
+
+    /// Get the stored hashes as digests.
+    pub fn get_digests(&self) -> &Vec<TlshDigest> {
+        &self.entries
     }
 }
 
@@ -59,43 +61,55 @@ impl ObjectImpl for SimpleTLSHObject {
     fn to_object(self) -> Object {
         Object {
             format: 0x0001,
-            compression_type: ObjectCompressionType::NoCompression,
+            // Compressed objects could not be serialized without the feature
+            compression_type: if self.compressed && cfg!(feature = "compression") {
+                DEFLATE
+            } else {
+                ObjectCompressionType::NoCompression
+            },
             entry_type: self.entry_type.as_value(),
-            entry_size: 70,
+            entry_size: self.entry_type.entry_len(),
             data: self
                 .entries
                 .into_iter()
-                .map(|e| {
-                    let Hex(s) = e else {
-                    panic!("invalid entry");
-                };
-                    s.as_bytes().to_vec()
+                .map(|e| match self.entry_type {
+                    Hex(_) => e.to_hex().into_bytes(),
+                    Raw(_) => e.as_bytes().to_vec(),
                 })
                 .collect(),
         }
     }
 
     fn from_object(obj: Object) -> Option<Self> {
-        if obj.format != 0x0001 {
-            return None;
-        }
+        Self::try_from_object(&obj).ok()
+    }
+}
+
+impl ObjectFormat for SimpleTLSHObject {
+    const FORMAT: u16 = 0x0001;
+    const ENTRY_TYPES: &'static [u16] = &[0, 1];
+
+    /// Every entry must be a valid standard digest, in hex or in binary depending on the entry
+    /// type.
+    fn try_from_object(obj: &Object) -> Result<Self, FormatError> {
+        Self::check_object(obj)?;
         let format = if obj.entry_type == 0 {
             SimpleTLSHEntryType::HEX
-        } else if obj.entry_type == 1 {
-            SimpleTLSHEntryType::RAW
         } else {
-            return None;
+            SimpleTLSHEntryType::RAW
         };
-        assert_eq!(obj.entry_size, format.entry_len());
+        check_entry_size(obj, format.entry_len())?;
         let mut entries = Vec::new();
-        for entry in obj.data {
-            if matches!(format, SimpleTLSHEntryType::Hex(_)) {
-                entries.push(Hex(String::from_utf8(entry).unwrap()))
-            } else {
-                entries.push(Raw(entry))
-            }
+        for (index, entry) in obj.data.iter().enumerate() {
+            let digest = match format {
+                Hex(_) => std::str::from_utf8(entry)
+                    .ok()
+                    .and_then(|hash| TlshDigest::from_hex(hash).ok()),
+                Raw(_) => TlshDigest::from_bytes(entry).ok(),
+            };
+            entries.push(digest.ok_or(FormatError::MalformedEntry(index))?);
         }
-        Some(Self {
+        Ok(Self {
             entries,
             entry_type: format,
             compressed: matches!(obj.compression_type, DEFLATE),
@@ -114,7 +128,8 @@ impl SimpleTLSHObject {
         }
     }
 
-    /// New, empty TLSH list with compressed storage enabled.
+    /// New, empty TLSH list with compressed storage enabled. Without the `compression` feature
+    /// the object is stored uncompressed.
     pub fn new_compressed(entry_type: SimpleTLSHEntryType) -> Self {
         Self {
             entries: Vec::new(),
@@ -123,50 +138,74 @@ impl SimpleTLSHObject {
         }
     }
 
-    /// Enable or disable object compression for this object.
+    /// Enable or disable object compression for this object. Without the `compression` feature
+    /// the object is stored uncompressed.
     pub fn set_compressed(&mut self, compressed: bool) {
         self.compressed = compressed;
     }
 
-    /// Add a hex String hash to the database. If the specified storage mode was RAW, the hash will
-    /// be converted to binary.
+    /// Add a hex String hash to the database, with or without the `T1` prefix. If the specified
+    /// storage mode was RAW, the hash will be converted to binary.
+    ///
+    /// Panics if the hash is not a valid standard TLSH digest.
     pub fn add_hash(&mut self, hash: String) {
-        if hash.len() != 70 {
-            panic!("invalid tlsh hash");
-        }
-
-        if matches!(self.entry_type, SimpleTLSHEntryType::Raw(_)) {
-            self.entries.push(Raw(decode_hex(&hash).unwrap()));
-        } else {
-            self.entries.push(Hex(hash));
+        match TlshDigest::from_hex(&hash) {
+            Ok(digest) => self.add_digest(digest),
+            Err(_) => panic!("invalid tlsh hash"),
         }
     }
 
     /// Add a raw bytes hash to the database. If the specified storage mode was HEX, the hash will
     /// be converted to hexadecimal format.
+    ///
+    /// Panics if the hash is not 35 bytes long.
     pub fn add_raw_hash(&mut self, hash: &[u8]) {
-        if hash.len() != 35 {
-            panic!("invalid tlsh hash");
-        }
-        if matches!(self.entry_type, SimpleTLSHEntryType::Raw(_)) {
-            self.entries.push(Raw(hash.to_vec()));
-        } else {
-            self.entries.push(Hex(encode_hex(hash)));
+        match TlshDigest::from_bytes(hash) {
+            Ok(digest) => self.add_digest(digest),
+            Err(_) => panic!("invalid tlsh hash"),
         }
     }
-}
 
-fn decode_hex(s: &str) -> Result<Vec<u8>, ParseIntError> {
-    (0..s.len())
-        .step_by(2)
-        .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
-        .collect()
+    /// Add a digest to the database.
+    ///
+    /// Panics if the digest is colored.
+    pub fn add_digest(&mut self, digest: TlshDigest) {
+        assert!(!digest.is_colored(), "colored tlsh hash");
+        self.entries.push(digest);
+    }
 }
 
-fn encode_hex(bytes: &[u8]) -> String {
-    let mut s = String::with_capacity(bytes.len() * 2);
-    for &b in bytes {
-        write!(&mut s, "{:02x}", b).unwrap();
+#[cfg(test)]
+mod test {
+    use crate::database::{FormatError, ObjectFormat, ObjectImpl};
+    use crate::formats::simple_tlsh::{SimpleTLSHEntryType, SimpleTLSHObject};
+
+    const HASH: &str = "B911A8DACB5B5A06568B6ED299B18014C811DD897E95B720B871B1F5EF7300538187DC";
+
+    #[test]
+    pub fn test_add_hash() {
+        let mut object = SimpleTLSHObject::new(SimpleTLSHEntryType::HEX);
+        object.add_hash(HASH.to_lowercase());
+        object.add_hash(format!("T1{}", HASH));
+        assert_eq!(
+            object.get_hashes(),
+            vec![HASH.to_string(), HASH.to_string()]
+        );
+        let object = object.to_object();
+        assert_eq!(object.data[0], HASH.as_bytes());
+
+        let mut invalid = object.clone();
+        invalid.data[1][0] = b'+';
+        assert_eq!(
+            SimpleTLSHObject::try_from_object(&invalid).err(),
+            Some(FormatError::MalformedEntry(1))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid tlsh hash")]
+    pub fn test_add_invalid_hash() {
+        let mut object = SimpleTLSHObject::new(SimpleTLSHEntryType::RAW);
+        object.add_hash(format!("{}G0", &HASH[2..]));
     }
-    s
 }
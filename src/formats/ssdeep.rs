@@ -0,0 +1,152 @@
+use crate::database::{
+    check_entry_size, FormatError, Object, ObjectCompressionType, ObjectFormat, ObjectImpl,
+};
+use crate::hash::Sha256;
+use crate::ssdeep::{Ssdeep, SPAMSUM_HALF_LENGTH, SPAMSUM_LENGTH};
+
+const ENTRY_SIZE: usize = 4 + SPAMSUM_LENGTH + SPAMSUM_HALF_LENGTH + 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SsdeepEntry {
+    pub digest: Ssdeep,
+    pub sha256_hash: Sha256,
+}
+
+/// Object format 0x0007, Ssdeep.
+///
+/// Stores a list of ssdeep digests with the SHA256 hash of the sample. The digests are stored in
+/// fixed size entries, the hash parts are padded with zero bytes.
+pub struct SsdeepObject {
+    entries: Vec<SsdeepEntry>,
+}
+
+impl SsdeepObject {
+    pub fn empty() -> Self {
+        Self { entries: vec![] }
+    }
+
+    pub fn get_entries(&self) -> &Vec<SsdeepEntry> {
+        &self.entries
+    }
+
+    /// Add a digest in the `blocksize:hash1:hash2` format.
+    ///
+    /// Panics if the digest is invalid, use [`Ssdeep::parse`] to validate untrusted input.
+    pub fn add_entry(&mut self, ssdeep_hash: &str, sha_hash: impl Into<Sha256>) {
+        self.entries.push(SsdeepEntry {
+            digest: Ssdeep::parse(ssdeep_hash).expect("invalid ssdeep hash"),
+            sha256_hash: sha_hash.into(),
+        });
+    }
+
+    /// Every entry with a match score of at least `min_score`, the best match first.
+    pub fn matches(&self, digest: &Ssdeep, min_score: u32) -> Vec<(&SsdeepEntry, u32)> {
+        let mut matches: Vec<(&SsdeepEntry, u32)> = self
+            .entries
+            .iter()
+            .map(|e| (e, e.digest.compare(digest)))
+            .filter(|(_, score)| *score >= min_score)
+            .collect();
+        matches.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+        matches
+    }
+}
+
+impl ObjectImpl for SsdeepObject {
+    const NAME: &'static str = "Ssdeep";
+
+    fn to_object(self) -> Object {
+        Object {
+            format: 0x0007,
+            compression_type: ObjectCompressionType::NoCompression,
+            entry_type: 0,
+            entry_size: ENTRY_SIZE as u16,
+            data: self
+                .entries
+                .into_iter()
+                .map(|e| {
+                    let mut e_vec = e.digest.block_size().to_be_bytes().to_vec();
+                    e_vec.extend_from_slice(e.digest.part1().as_bytes());
+                    e_vec.resize(4 + SPAMSUM_LENGTH, 0);
+                    e_vec.extend_from_slice(e.digest.part2().as_bytes());
+                    e_vec.resize(4 + SPAMSUM_LENGTH + SPAMSUM_HALF_LENGTH, 0);
+                    e_vec.extend_from_slice(e.sha256_hash.as_bytes());
+                    e_vec
+                })
+                .collect(),
+        }
+    }
+
+    fn from_object(obj: Object) -> Option<Self> {
+        Self::try_from_object(&obj).ok()
+    }
+}
+
+impl ObjectFormat for SsdeepObject {
+    const FORMAT: u16 = 0x0007;
+    const ENTRY_TYPES: &'static [u16] = &[0];
+
+    /// The digests must be valid ssdeep digests.
+    fn try_from_object(obj: &Object) -> Result<Self, FormatError> {
+        Self::check_object(obj)?;
+        check_entry_size(obj, ENTRY_SIZE as u16)?;
+
+        let entries = obj
+            .data
+            .iter()
+            .enumerate()
+            .map(|(index, e)| parse_entry(e).ok_or(FormatError::MalformedEntry(index)))
+            .collect::<Result<Vec<SsdeepEntry>, FormatError>>()?;
+
+        Ok(Self { entries })
+    }
+}
+
+fn parse_entry(entry: &[u8]) -> Option<SsdeepEntry> {
+    let (part1, rest) = entry[4..].split_at(SPAMSUM_LENGTH);
+    let (part2, sha256_hash) = rest.split_at(SPAMSUM_HALF_LENGTH);
+    let digest = Ssdeep::new(
+        u32::from_be_bytes(entry[0..4].try_into().unwrap()),
+        std::str::from_utf8(trim_padding(part1)).ok()?,
+        std::str::from_utf8(trim_padding(part2)).ok()?,
+    )
+    .ok()?;
+    Some(SsdeepEntry {
+        digest,
+        sha256_hash: Sha256::from_bytes(sha256_hash).unwrap(),
+    })
+}
+
+fn trim_padding(part: &[u8]) -> &[u8] {
+    let end = part.iter().position(|b| *b == 0).unwrap_or(part.len());
+    &part[..end]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::database::ObjectImpl;
+    use crate::formats::ssdeep::SsdeepObject;
+    use crate::hash::Sha256;
+    use crate::ssdeep::Ssdeep;
+
+    #[test]
+    pub fn test_ssdeep_object() {
+        let mut object = SsdeepObject::empty();
+        object.add_entry("48:abcdefghij:qqqqqqq", [1; 32]);
+        object.add_entry("48:abcdefghXY:rrrrrrr", [2; 32]);
+        object.add_entry(&format!("3:{}:{}", "A".repeat(64), "B".repeat(32)), [3; 32]);
+
+        let object = SsdeepObject::from_object(object.to_object()).unwrap();
+        assert_eq!(object.get_entries().len(), 3);
+        assert_eq!(object.get_entries()[2].digest.part1().len(), 64);
+        assert_eq!(object.get_entries()[1].sha256_hash, Sha256::from([2; 32]));
+
+        let query = Ssdeep::parse("48:abcdefghij:sssssss").unwrap();
+        let matches: Vec<([u8; 32], u32)> = object
+            .matches(&query, 50)
+            .iter()
+            .map(|(e, score)| (*e.sha256_hash.as_bytes(), *score))
+            .collect();
+        assert_eq!(matches, vec![([1; 32], 100), ([2; 32], 82)]);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::database::{
+    check_entry_size, FormatError, Object, ObjectCompressionType, ObjectFormat, ObjectImpl,
+    VARIABLE_ENTRY_SIZE,
+};
+use std::collections::HashMap;
+
+/// Object format 0x0009, StringTable.
+///
+/// Stores deduplicated UTF-8 strings, e.g. malware family names and tags, addressed by their
+/// index. Other objects, like [`LabelMapObject`](crate::formats::label_map::LabelMapObject),
+/// refer to the strings by index instead of storing them repeatedly.
+pub struct StringTableObject {
+    strings: Vec<String>,
+    indices: HashMap<String, u32>,
+}
+
+impl StringTableObject {
+    pub fn empty() -> Self {
+        Self {
+            strings: vec![],
+            indices: HashMap::new(),
+        }
+    }
+
+    pub fn get_strings(&self) -> &Vec<String> {
+        &self.strings
+    }
+
+    /// Add a string to the table if it is not already present, returning its index.
+    pub fn intern(&mut self, string: &str) -> u32 {
+        if let Some(index) = self.indices.get(string) {
+            return *index;
+        }
+        let index = self.strings.len() as u32;
+        self.strings.push(string.to_string());
+        self.indices.insert(string.to_string(), index);
+        index
+    }
+
+    /// Get the string with the specified index.
+    pub fn get(&self, index: u32) -> Option<&str> {
+        self.strings.get(index as usize).map(|s| s.as_str())
+    }
+
+    /// Get the index of a string, if it is present.
+    pub fn find(&self, string: &str) -> Option<u32> {
+        self.indices.get(string).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+/// Get a string from a generic string table object without decoding the whole table.
+///
+/// Returns none if the object is not a string table or the entry is not valid UTF-8.
+pub(crate) fn lookup(obj: &Object, index: u32) -> Option<&str> {
+    if !is_string_table(obj) {
+        return None;
+    }
+    std::str::from_utf8(obj.data.get(index as usize)?).ok()
+}
+
+fn is_string_table(obj: &Object) -> bool {
+    obj.format == 0x0009 && obj.entry_type == 0 && obj.entry_size == VARIABLE_ENTRY_SIZE
+}
+
+impl ObjectImpl for StringTableObject {
+    const NAME: &'static str = "StringTable";
+
+    fn to_object(self) -> Object {
+        Object {
+            format: 0x0009,
+            compression_type: ObjectCompressionType::NoCompression,
+            entry_type: 0,
+            entry_size: VARIABLE_ENTRY_SIZE,
+            data: self.strings.into_iter().map(|s| s.into_bytes()).collect(),
+        }
+    }
+
+    fn from_object(obj: Object) -> Option<Self> {
+        Self::try_from_object(&obj).ok()
+    }
+}
+
+impl ObjectFormat for StringTableObject {
+    const FORMAT: u16 = 0x0009;
+    const ENTRY_TYPES: &'static [u16] = &[0];
+
+    /// The strings must be valid UTF-8 and stored only once.
+    fn try_from_object(obj: &Object) -> Result<Self, FormatError> {
+        Self::check_object(obj)?;
+        check_entry_size(obj, VARIABLE_ENTRY_SIZE)?;
+
+        let mut table = Self::empty();
+        for (index, entry) in obj.data.iter().enumerate() {
+            match std::str::from_utf8(entry) {
+                Ok(string) if table.find(string).is_none() => table.intern(string),
+                _ => return Err(FormatError::MalformedEntry(index)),
+            };
+        }
+
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::database::{FormatError, ObjectFormat, ObjectImpl};
+    use crate::formats::string_table::{lookup, StringTableObject};
+
+    #[test]
+    pub fn test_string_table() {
+        let mut table = StringTableObject::empty();
+        assert_eq!(table.intern("Mirai.B"), 0);
+        assert_eq!(table.intern("botnet"), 1);
+        assert_eq!(table.intern("Mirai.B"), 0);
+        assert_eq!(table.intern(""), 2);
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.find("botnet"), Some(1));
+        assert_eq!(table.find("Gafgyt"), None);
+
+        let object = table.to_object();
+        assert_eq!(lookup(&object, 0), Some("Mirai.B"));
+        assert_eq!(lookup(&object, 3), None);
+
+        let table = StringTableObject::try_from_object(&object).unwrap();
+        assert_eq!(table.get(1), Some("botnet"));
+        assert_eq!(table.get(2), Some(""));
+
+        let mut duplicated = object.clone();
+        duplicated.data.push(b"botnet".to_vec());
+        assert_eq!(
+            StringTableObject::try_from_object(&duplicated).err(),
+            Some(FormatError::MalformedEntry(3))
+        );
+
+        let mut invalid = object;
+        invalid.data.push(vec![0xff, 0xfe]);
+        assert_eq!(
+            StringTableObject::try_from_object(&invalid).err(),
+            Some(FormatError::MalformedEntry(3))
+        );
+    }
+}
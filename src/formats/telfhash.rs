@@ -0,0 +1,87 @@
+use crate::database::{
+    check_entry_size, FormatError, Object, ObjectCompressionType, ObjectFormat, ObjectImpl,
+};
+use crate::hash::{Sha256, TlshDigest};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TelfhashEntry {
+    /// The standard 35-byte digest.
+    pub telfhash: TlshDigest,
+    pub sha256_hash: Sha256,
+}
+
+/// Object format 0x0008, Telfhash.
+///
+/// Stores a list of telfhash digests (standard 35-byte TLSH digests of the ELF symbol list) in
+/// binary format with the SHA256 hash of the sample. The digests can be computed with
+/// `telfhash::telfhash` if the `telfhash` feature is enabled.
+pub struct TelfhashObject {
+    entries: Vec<TelfhashEntry>,
+}
+
+impl TelfhashObject {
+    pub fn empty() -> Self {
+        Self { entries: vec![] }
+    }
+
+    pub fn get_entries(&self) -> &Vec<TelfhashEntry> {
+        &self.entries
+    }
+
+    /// Panics if the digest is colored.
+    pub fn add_entry(&mut self, telfhash: impl Into<TlshDigest>, sha_hash: impl Into<Sha256>) {
+        let telfhash = telfhash.into();
+        assert!(!telfhash.is_colored(), "colored telfhash");
+        self.entries.push(TelfhashEntry {
+            telfhash,
+            sha256_hash: sha_hash.into(),
+        });
+    }
+}
+
+impl ObjectImpl for TelfhashObject {
+    const NAME: &'static str = "Telfhash";
+
+    fn to_object(self) -> Object {
+        Object {
+            format: 0x0008,
+            compression_type: ObjectCompressionType::NoCompression,
+            entry_type: 0,
+            entry_size: 35 + 32,
+            data: self
+                .entries
+                .into_iter()
+                .map(|e| {
+                    let mut e_vec = e.telfhash.as_bytes().to_vec();
+                    e_vec.extend_from_slice(e.sha256_hash.as_bytes());
+                    e_vec
+                })
+                .collect(),
+        }
+    }
+
+    fn from_object(obj: Object) -> Option<Self> {
+        Self::try_from_object(&obj).ok()
+    }
+}
+
+impl ObjectFormat for TelfhashObject {
+    const FORMAT: u16 = 0x0008;
+    const ENTRY_TYPES: &'static [u16] = &[0];
+
+    fn try_from_object(obj: &Object) -> Result<Self, FormatError> {
+        Self::check_object(obj)?;
+        check_entry_size(obj, 35 + 32)?;
+
+        let entries = obj
+            .data
+            .iter()
+            .map(|entry| TelfhashEntry {
+                telfhash: TlshDigest::from_bytes(&entry[0..35]).unwrap(),
+                sha256_hash: Sha256::from_bytes(&entry[35..35 + 32]).unwrap(),
+            })
+            .collect();
+
+        Ok(Self { entries })
+    }
+}
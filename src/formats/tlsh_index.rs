@@ -0,0 +1,177 @@
+use crate::database::{
+    check_entry_size, FormatError, Object, ObjectCompressionType, ObjectFormat, ObjectImpl,
+};
+use crate::search::{build_tree, indexed_entries, IndexNode};
+
+/// Object format 0x0004, TLSHIndex.
+///
+/// Stores a prebuilt [`TlshIndex`](crate::search::TlshIndex) of another TLSH object in the same
+/// database, so it does not need to be rebuilt on every load. The first entry references the
+/// indexed object by ID, together with its entry count and checksum at the time the index was
+/// built. Every other entry is a node of the flattened BK-tree.
+pub struct TLSHIndexObject {
+    indexed_object: u64,
+    entry_count: u32,
+    checksum: u32,
+    pub(crate) nodes: Vec<IndexNode>,
+}
+
+impl TLSHIndexObject {
+    /// Build the index of a TLSH object stored with the ID `indexed_object`.
+    ///
+    /// Returns none if the object is not a supported TLSH object.
+    pub fn new(indexed_object: u64, indexed: &Object) -> Option<Self> {
+        let entries = indexed_entries(indexed)?;
+        Some(Self {
+            indexed_object,
+            entry_count: indexed.data.len() as u32,
+            checksum: object_checksum(indexed),
+            nodes: build_tree(&entries),
+        })
+    }
+
+    /// ID of the indexed object.
+    pub fn indexed_object(&self) -> u64 {
+        self.indexed_object
+    }
+
+    /// Check whether the indexed object changed since the index was built.
+    pub fn is_stale(&self, indexed: &Object) -> bool {
+        indexed.data.len() as u64 != self.entry_count as u64
+            || object_checksum(indexed) != self.checksum
+    }
+}
+
+impl ObjectImpl for TLSHIndexObject {
+    const NAME: &'static str = "TLSHIndex";
+
+    fn to_object(self) -> Object {
+        let mut reference = self.indexed_object.to_be_bytes().to_vec();
+        reference.extend_from_slice(&self.entry_count.to_be_bytes());
+        reference.extend_from_slice(&self.checksum.to_be_bytes());
+
+        let mut data = vec![reference];
+        data.extend(self.nodes.into_iter().map(|n| {
+            let mut entry = n.entry.to_be_bytes().to_vec();
+            entry.extend_from_slice(&n.distance.to_be_bytes());
+            entry.extend_from_slice(&[0, 0]);
+            entry.extend_from_slice(&n.first_child.to_be_bytes());
+            entry.extend_from_slice(&n.child_count.to_be_bytes());
+            entry
+        }));
+
+        Object {
+            format: 0x0004,
+            compression_type: ObjectCompressionType::NoCompression,
+            entry_type: 0,
+            entry_size: 16,
+            data,
+        }
+    }
+
+    fn from_object(obj: Object) -> Option<Self> {
+        Self::try_from_object(&obj).ok()
+    }
+}
+
+impl ObjectFormat for TLSHIndexObject {
+    const FORMAT: u16 = 0x0004;
+    const ENTRY_TYPES: &'static [u16] = &[0];
+
+    /// The first entry references the indexed object, the nodes must form a tree in the layout
+    /// of the builder.
+    fn try_from_object(obj: &Object) -> Result<Self, FormatError> {
+        Self::check_object(obj)?;
+        check_entry_size(obj, 16)?;
+        let (reference, nodes) = obj
+            .data
+            .split_first()
+            .ok_or(FormatError::MalformedEntry(0))?;
+
+        let entry_count = u32::from_be_bytes(reference[8..12].try_into().unwrap());
+        if nodes.len() as u64 != entry_count as u64 {
+            return Err(FormatError::MalformedEntry(0));
+        }
+        let nodes: Vec<IndexNode> = nodes
+            .iter()
+            .map(|n| IndexNode {
+                entry: u32::from_be_bytes(n[0..4].try_into().unwrap()),
+                distance: u16::from_be_bytes(n[4..6].try_into().unwrap()),
+                first_child: u32::from_be_bytes(n[8..12].try_into().unwrap()),
+                child_count: u32::from_be_bytes(n[12..16].try_into().unwrap()),
+            })
+            .collect();
+
+        // Every reference must be in range, and the nodes must be in the breadth-first layout of
+        // the builder: the children of each node follow the children of the previous nodes, after
+        // the node itself. The nodes then form a tree, so the searches terminate.
+        let mut next_child = 1u64;
+        for (index, n) in nodes.iter().enumerate() {
+            if n.entry >= entry_count
+                || n.first_child as u64 != next_child
+                || n.first_child as u64 <= index as u64
+                || n.first_child as u64 + n.child_count as u64 > nodes.len() as u64
+            {
+                return Err(FormatError::MalformedEntry(index + 1));
+            }
+            next_child += n.child_count as u64;
+        }
+        if !nodes.is_empty() && next_child != nodes.len() as u64 {
+            return Err(FormatError::MalformedEntry(0));
+        }
+
+        Ok(Self {
+            indexed_object: u64::from_be_bytes(reference[0..8].try_into().unwrap()),
+            entry_count,
+            checksum: u32::from_be_bytes(reference[12..16].try_into().unwrap()),
+            nodes,
+        })
+    }
+}
+
+/// FNV-1a checksum of the decoded entries of an object.
+fn object_checksum(obj: &Object) -> u32 {
+    obj.data.iter().flatten().fold(0x811c9dc5u32, |hash, b| {
+        (hash ^ *b as u32).wrapping_mul(0x01000193)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::database::{FormatError, ObjectFormat, ObjectImpl};
+    use crate::formats::colored_tlsh::ColoredTLSHObject;
+    use crate::formats::tlsh_index::TLSHIndexObject;
+
+    #[test]
+    pub fn test_node_validation() {
+        let mut colored = ColoredTLSHObject::empty();
+        for i in 0..4u8 {
+            let mut digest = [0u8; 36];
+            digest[3] = i;
+            colored.add_entry(digest, [i; 32]);
+        }
+        let object = TLSHIndexObject::new(1, &colored.to_object())
+            .unwrap()
+            .to_object();
+        assert!(TLSHIndexObject::try_from_object(&object).is_ok());
+
+        // Node 0 as its own child
+        let mut cycle = object.clone();
+        cycle.data[1][8..12].copy_from_slice(&0u32.to_be_bytes());
+        assert_eq!(
+            TLSHIndexObject::try_from_object(&cycle).err(),
+            Some(FormatError::MalformedEntry(1))
+        );
+
+        // A child pointing back to its parent
+        let mut back_edge = object.clone();
+        let first_child = u32::from_be_bytes(object.data[1][8..12].try_into().unwrap()) as usize;
+        back_edge.data[first_child + 1][8..16].copy_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        assert!(TLSHIndexObject::try_from_object(&back_edge).is_err());
+
+        // Children shared by two nodes
+        let mut shared = object;
+        shared.data[1][12..16].copy_from_slice(&1u32.to_be_bytes());
+        assert!(TLSHIndexObject::try_from_object(&shared).is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use crate::database::ObjectCompressionType::DEFLATE;
+use crate::database::{
+    check_entry_size, FormatError, Object, ObjectCompressionType, ObjectFormat, ObjectImpl,
+    VARIABLE_ENTRY_SIZE,
+};
+
+/// Precompiled form of a [`YaraRuleSet`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledRules {
+    /// Version of the YARA compiler that produced the data. Compiled rules can only be loaded
+    /// by a compatible engine, the source should be used otherwise.
+    pub compiler_version: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct YaraRuleSet {
+    pub name: String,
+    pub source: String,
+    pub compiled: Option<CompiledRules>,
+}
+
+/// Object format 0x000B, YaraRules.
+///
+/// Stores named YARA rule sets as source text, optionally with a precompiled version. The rule
+/// set names are unique.
+///
+/// Entry type 0 only stores the sources, entry type 1 also stores the compiled rules. Type 1 is
+/// used when saving if any of the rule sets has compiled rules.
+pub struct YaraRulesObject {
+    rule_sets: Vec<YaraRuleSet>,
+    compressed: bool,
+}
+
+impl YaraRulesObject {
+    pub fn empty() -> Self {
+        Self {
+            rule_sets: vec![],
+            compressed: false,
+        }
+    }
+
+    /// Enable or disable object compression for this object. Without the `compression` feature
+    /// the object is stored uncompressed.
+    pub fn set_compressed(&mut self, compressed: bool) {
+        self.compressed = compressed;
+    }
+
+    pub fn get_rule_sets(&self) -> &Vec<YaraRuleSet> {
+        &self.rule_sets
+    }
+
+    /// Iterate over the rule sets in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = &YaraRuleSet> {
+        self.rule_sets.iter()
+    }
+
+    /// Get a rule set by its name.
+    pub fn get(&self, name: &str) -> Option<&YaraRuleSet> {
+        self.rule_sets.iter().find(|r| r.name == name)
+    }
+
+    /// Add a rule set from source, replacing the rule set with the same name.
+    ///
+    /// Panics if the name is longer than 65535 bytes.
+    pub fn add_rule_set(&mut self, name: &str, source: &str) {
+        self.insert(YaraRuleSet {
+            name: name.to_string(),
+            source: source.to_string(),
+            compiled: None,
+        });
+    }
+
+    /// Add a rule set with its source and compiled form, replacing the rule set with the same
+    /// name.
+    ///
+    /// Panics if the name or the compiler version is longer than 65535 bytes, or the compiler
+    /// version is empty.
+    pub fn add_compiled_rule_set(
+        &mut self,
+        name: &str,
+        source: &str,
+        compiler_version: &str,
+        data: &[u8],
+    ) {
+        assert!(!compiler_version.is_empty(), "empty compiler version");
+        assert!(
+            compiler_version.len() <= u16::MAX as usize,
+            "compiler version too long"
+        );
+        self.insert(YaraRuleSet {
+            name: name.to_string(),
+            source: source.to_string(),
+            compiled: Some(CompiledRules {
+                compiler_version: compiler_version.to_string(),
+                data: data.to_vec(),
+            }),
+        });
+    }
+
+    fn insert(&mut self, rule_set: YaraRuleSet) {
+        assert!(
+            rule_set.name.len() <= u16::MAX as usize,
+            "rule set name too long"
+        );
+        match self.rule_sets.iter().position(|r| r.name == rule_set.name) {
+            Some(position) => self.rule_sets[position] = rule_set,
+            None => self.rule_sets.push(rule_set),
+        }
+    }
+}
+
+/// Serialize a rule set: the name length (u16) and name, the source length (u32) and source,
+/// then for entry type 1 the compiler version length (u16), compiler version and compiled data.
+fn encode_rule_set(rule_set: YaraRuleSet, entry_type: u16) -> Vec<u8> {
+    let mut e_vec = (rule_set.name.len() as u16).to_be_bytes().to_vec();
+    e_vec.extend_from_slice(rule_set.name.as_bytes());
+    e_vec.extend_from_slice(&(rule_set.source.len() as u32).to_be_bytes());
+    e_vec.extend_from_slice(rule_set.source.as_bytes());
+    if entry_type == 1 {
+        let (compiler_version, data) = rule_set
+            .compiled
+            .map_or((String::new(), vec![]), |c| (c.compiler_version, c.data));
+        e_vec.extend_from_slice(&(compiler_version.len() as u16).to_be_bytes());
+        e_vec.extend_from_slice(compiler_version.as_bytes());
+        e_vec.extend_from_slice(&data);
+    }
+    e_vec
+}
+
+fn decode_rule_set(mut entry: &[u8], entry_type: u16) -> Option<YaraRuleSet> {
+    let name_len = u16::from_be_bytes(take(&mut entry, 2)?.try_into().unwrap());
+    let name = std::str::from_utf8(take(&mut entry, name_len as usize)?).ok()?;
+    let source_len = u32::from_be_bytes(take(&mut entry, 4)?.try_into().unwrap());
+    let source = std::str::from_utf8(take(&mut entry, source_len as usize)?).ok()?;
+    let compiled = if entry_type == 1 {
+        let version_len = u16::from_be_bytes(take(&mut entry, 2)?.try_into().unwrap());
+        let compiler_version = std::str::from_utf8(take(&mut entry, version_len as usize)?).ok()?;
+        let data = std::mem::take(&mut entry);
+        (!compiler_version.is_empty()).then(|| CompiledRules {
+            compiler_version: compiler_version.to_string(),
+            data: data.to_vec(),
+        })
+    } else {
+        None
+    };
+    if !entry.is_empty() {
+        return None;
+    }
+    Some(YaraRuleSet {
+        name: name.to_string(),
+        source: source.to_string(),
+        compiled,
+    })
+}
+
+/// Split the first `len` bytes off the entry.
+fn take<'a>(entry: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if entry.len() < len {
+        return None;
+    }
+    let (head, tail) = entry.split_at(len);
+    *entry = tail;
+    Some(head)
+}
+
+impl ObjectImpl for YaraRulesObject {
+    const NAME: &'static str = "YaraRules";
+
+    fn to_object(self) -> Object {
+        let entry_type = if self.rule_sets.iter().any(|r| r.compiled.is_some()) {
+            1
+        } else {
+            0
+        };
+        Object {
+            format: 0x000B,
+            // Compressed objects could not be serialized without the feature
+            compression_type: if self.compressed && cfg!(feature = "compression") {
+                DEFLATE
+            } else {
+                ObjectCompressionType::NoCompression
+            },
+            entry_type,
+            entry_size: VARIABLE_ENTRY_SIZE,
+            data: self
+                .rule_sets
+                .into_iter()
+                .map(|r| encode_rule_set(r, entry_type))
+                .collect(),
+        }
+    }
+
+    fn from_object(obj: Object) -> Option<Self> {
+        Self::try_from_object(&obj).ok()
+    }
+}
+
+impl ObjectFormat for YaraRulesObject {
+    const FORMAT: u16 = 0x000B;
+    const ENTRY_TYPES: &'static [u16] = &[0, 1];
+
+    /// Every rule set name must be used only once.
+    fn try_from_object(obj: &Object) -> Result<Self, FormatError> {
+        Self::check_object(obj)?;
+        check_entry_size(obj, VARIABLE_ENTRY_SIZE)?;
+
+        let mut rule_sets: Vec<YaraRuleSet> = Vec::new();
+        for (index, entry) in obj.data.iter().enumerate() {
+            match decode_rule_set(entry, obj.entry_type) {
+                Some(rule_set) if !rule_sets.iter().any(|r| r.name == rule_set.name) => {
+                    rule_sets.push(rule_set)
+                }
+                _ => return Err(FormatError::MalformedEntry(index)),
+            }
+        }
+
+        Ok(Self {
+            rule_sets,
+            compressed: matches!(obj.compression_type, DEFLATE),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::database::{FormatError, ObjectFormat, ObjectImpl};
+    use crate::formats::yara_rules::YaraRulesObject;
+
+    const MIRAI_RULE: &str = "rule mirai { strings: $a = \"/bin/busybox MIRAI\" condition: $a }";
+
+    #[test]
+    pub fn test_yara_rules() {
+        let mut rules = YaraRulesObject::empty();
+        rules.add_rule_set("mirai", "rule mirai { condition: false }");
+        rules.add_rule_set("empty", "");
+        rules.add_rule_set("mirai", MIRAI_RULE);
+        let object = rules.to_object();
+        assert_eq!(object.entry_type, 0);
+
+        let rules = YaraRulesObject::try_from_object(&object).unwrap();
+        let names: Vec<&str> = rules.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["mirai", "empty"]);
+        assert_eq!(rules.get("mirai").unwrap().source, MIRAI_RULE);
+        assert!(rules.get("gafgyt").is_none());
+
+        let mut duplicated = object.clone();
+        duplicated.data.push(object.data[0].clone());
+        assert_eq!(
+            YaraRulesObject::try_from_object(&duplicated).err(),
+            Some(FormatError::MalformedEntry(2))
+        );
+
+        let mut truncated = object;
+        truncated.data[0].pop();
+        assert_eq!(
+            YaraRulesObject::try_from_object(&truncated).err(),
+            Some(FormatError::MalformedEntry(0))
+        );
+    }
+
+    #[test]
+    pub fn test_compiled_rules() {
+        let mut rules = YaraRulesObject::empty();
+        rules.add_rule_set("source_only", MIRAI_RULE);
+        rules.add_compiled_rule_set("compiled", MIRAI_RULE, "4.5.0", &[0x59, 0x41, 0x52, 0x41]);
+        let object = rules.to_object();
+        assert_eq!(object.entry_type, 1);
+
+        let rules = YaraRulesObject::from_object(object).unwrap();
+        assert!(rules.get("source_only").unwrap().compiled.is_none());
+        let compiled = rules.get("compiled").unwrap().compiled.as_ref().unwrap();
+        assert_eq!(compiled.compiler_version, "4.5.0");
+        assert_eq!(compiled.data, vec![0x59, 0x41, 0x52, 0x41]);
+        assert_eq!(rules.get("compiled").unwrap().source, MIRAI_RULE);
+    }
+}
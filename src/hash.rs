@@ -0,0 +1,306 @@
+//! Validated hash value types used by the object formats.
+//!
+//! [`Sha256`] is a SHA256 hash, [`TlshDigest`] is a binary TLSH digest in the stored form of
+//! the formats, either the standard 35-byte or the colored 36-byte variant (see the
+//! [`tlsh`](crate::tlsh) module). Both can be parsed from and displayed as hex, and are ordered by
+//! their bytes.
+//!
+//! # Example: Parse hashes
+//! ```rust
+//! use simbiota_database::hash::{Sha256, TlshDigest};
+//!
+//! let digest: TlshDigest = "T1B911A8DACB5B5A06568B6ED299B18014C811DD897E95B720B871B1F5EF7300538187DC"
+//!     .parse()
+//!     .unwrap();
+//! assert!(!digest.is_colored());
+//! assert_eq!(digest.as_bytes()[0], 0xB9);
+//!
+//! let sha256 = Sha256::from_hex(&"ab".repeat(32)).unwrap();
+//! assert_eq!(sha256, Sha256::from([0xab; 32]));
+//! assert!(Sha256::from_hex("abc").is_err());
+//! ```
+
+use crate::hash::HashParseError::{InvalidHex, InvalidLength};
+use crate::tlsh::{Tlsh, COLORED_TLSH_LENGTH, TLSH_LENGTH};
+use std::fmt::{Display, Formatter, Write};
+use std::str::FromStr;
+
+/// Error representing failures that can occur while parsing a hash.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HashParseError {
+    /// The hash is not of a valid length, in bytes for binary and characters for hex input.
+    InvalidLength(usize),
+    /// The hash string contains a non-hexadecimal character.
+    InvalidHex,
+}
+
+/// A SHA256 hash.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Sha256([u8; 32]);
+
+impl Sha256 {
+    /// Length of the hash in bytes.
+    pub const LENGTH: usize = 32;
+
+    pub const fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, HashParseError> {
+        Ok(Self(
+            bytes.try_into().map_err(|_| InvalidLength(bytes.len()))?,
+        ))
+    }
+
+    /// Parse a 64 character hex hash, in either case.
+    pub fn from_hex(hex: &str) -> Result<Self, HashParseError> {
+        if hex.len() != Self::LENGTH * 2 {
+            return Err(InvalidLength(hex.len()));
+        }
+        Self::from_bytes(&decode_hex(hex)?)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// The lowercase hex hash.
+    pub fn to_hex(&self) -> String {
+        encode_hex(&self.0).to_lowercase()
+    }
+}
+
+impl From<[u8; 32]> for Sha256 {
+    fn from(value: [u8; 32]) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&[u8; 32]> for Sha256 {
+    fn from(value: &[u8; 32]) -> Self {
+        Self(*value)
+    }
+}
+
+impl TryFrom<&[u8]> for Sha256 {
+    type Error = HashParseError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        Self::from_bytes(value)
+    }
+}
+
+impl AsRef<[u8]> for Sha256 {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl FromStr for Sha256 {
+    type Err = HashParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
+
+impl Display for Sha256 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+/// A binary TLSH digest, as stored in the objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TlshDigest {
+    /// The standard 35-byte digest.
+    Standard([u8; TLSH_LENGTH]),
+    /// The 36-byte colored digest, a standard digest followed by its colour.
+    Colored([u8; COLORED_TLSH_LENGTH]),
+}
+
+impl TlshDigest {
+    /// Parse a 35 or 36-byte binary digest.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, HashParseError> {
+        match bytes.len() {
+            TLSH_LENGTH => Ok(Self::Standard(bytes.try_into().unwrap())),
+            COLORED_TLSH_LENGTH => Ok(Self::Colored(bytes.try_into().unwrap())),
+            len => Err(InvalidLength(len)),
+        }
+    }
+
+    /// Parse a 70 or 72 character hex digest, in either case. The `T1` version prefix is
+    /// accepted, but not required.
+    pub fn from_hex(hex: &str) -> Result<Self, HashParseError> {
+        let hex = hex.strip_prefix("T1").unwrap_or(hex);
+        if hex.len() != TLSH_LENGTH * 2 && hex.len() != COLORED_TLSH_LENGTH * 2 {
+            return Err(InvalidLength(hex.len()));
+        }
+        Self::from_bytes(&decode_hex(hex)?)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            TlshDigest::Standard(bytes) => bytes,
+            TlshDigest::Colored(bytes) => bytes,
+        }
+    }
+
+    /// Whether the digest is the 36-byte colored variant.
+    pub fn is_colored(&self) -> bool {
+        matches!(self, TlshDigest::Colored(_))
+    }
+
+    /// The uppercase hex digest, without the `T1` prefix.
+    pub fn to_hex(&self) -> String {
+        encode_hex(self.as_bytes())
+    }
+
+    /// Parse the digest for distance computation.
+    pub fn to_tlsh(&self) -> Tlsh {
+        Tlsh::from_bytes(self.as_bytes()).unwrap()
+    }
+}
+
+impl From<[u8; TLSH_LENGTH]> for TlshDigest {
+    fn from(value: [u8; TLSH_LENGTH]) -> Self {
+        Self::Standard(value)
+    }
+}
+
+impl From<&[u8; TLSH_LENGTH]> for TlshDigest {
+    fn from(value: &[u8; TLSH_LENGTH]) -> Self {
+        Self::Standard(*value)
+    }
+}
+
+impl From<[u8; COLORED_TLSH_LENGTH]> for TlshDigest {
+    fn from(value: [u8; COLORED_TLSH_LENGTH]) -> Self {
+        Self::Colored(value)
+    }
+}
+
+impl From<&[u8; COLORED_TLSH_LENGTH]> for TlshDigest {
+    fn from(value: &[u8; COLORED_TLSH_LENGTH]) -> Self {
+        Self::Colored(*value)
+    }
+}
+
+impl TryFrom<&[u8]> for TlshDigest {
+    type Error = HashParseError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        Self::from_bytes(value)
+    }
+}
+
+impl AsRef<[u8]> for TlshDigest {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl FromStr for TlshDigest {
+    type Err = HashParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
+
+impl Display for TlshDigest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+/// Decode a hex string, in either case.
+pub(crate) fn decode_hex(hex: &str) -> Result<Vec<u8>, HashParseError> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(InvalidLength(hex.len()));
+    }
+    let digit = |c: u8| (c as char).to_digit(16).ok_or(InvalidHex);
+    hex.as_bytes()
+        .chunks_exact(2)
+        .map(|pair| Ok((digit(pair[0])? << 4 | digit(pair[1])?) as u8))
+        .collect()
+}
+
+/// Encode bytes as an uppercase hex string.
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for &b in bytes {
+        write!(&mut s, "{:02X}", b).unwrap();
+    }
+    s
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hash::{decode_hex, HashParseError, Sha256, TlshDigest};
+    use std::collections::HashSet;
+
+    const HASH: &str = "B911A8DACB5B5A06568B6ED299B18014C811DD897E95B720B871B1F5EF7300538187DC";
+
+    #[test]
+    pub fn test_decode_hex() {
+        assert_eq!(decode_hex("00fFa1"), Ok(vec![0x00, 0xff, 0xa1]));
+        assert_eq!(decode_hex("abc"), Err(HashParseError::InvalidLength(3)));
+        assert_eq!(decode_hex("+f"), Err(HashParseError::InvalidHex));
+        // Multi-byte characters must not be sliced
+        assert_eq!(decode_hex("aéb"), Err(HashParseError::InvalidHex));
+        assert_eq!(decode_hex("é"), Err(HashParseError::InvalidHex));
+    }
+
+    #[test]
+    pub fn test_tlsh_digest() {
+        let digest = TlshDigest::from_hex(HASH).unwrap();
+        assert!(!digest.is_colored());
+        assert_eq!(digest.to_hex(), HASH);
+        assert_eq!(digest.to_string(), HASH);
+        assert_eq!(TlshDigest::from_hex(&format!("T1{}", HASH)), Ok(digest));
+        assert_eq!(TlshDigest::from_hex(&HASH.to_lowercase()), Ok(digest));
+        assert_eq!(TlshDigest::from_bytes(digest.as_bytes()), Ok(digest));
+        assert_eq!(digest.to_tlsh().to_hex(), HASH);
+
+        let colored = TlshDigest::from_hex(&format!("{}01", HASH)).unwrap();
+        assert!(colored.is_colored());
+        assert_eq!(colored.as_bytes().len(), 36);
+        assert_eq!(colored.to_tlsh().colour(), Some(1));
+        assert_eq!(colored.to_tlsh().distance(&digest.to_tlsh()), 0);
+
+        assert_eq!(
+            TlshDigest::from_hex(&HASH[2..]),
+            Err(HashParseError::InvalidLength(68))
+        );
+        assert_eq!(
+            TlshDigest::from_hex(&format!("{}G0", &HASH[2..])),
+            Err(HashParseError::InvalidHex)
+        );
+        assert_eq!(
+            TlshDigest::from_bytes(&[0; 34]),
+            Err(HashParseError::InvalidLength(34))
+        );
+        assert!(TlshDigest::from([0u8; 35]) < TlshDigest::from([0u8; 36]));
+    }
+
+    #[test]
+    pub fn test_sha256() {
+        let hex = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        let hash: Sha256 = hex.parse().unwrap();
+        assert_eq!(hash.as_bytes()[0], 0xe3);
+        assert_eq!(hash.to_string(), hex);
+        assert_eq!(Sha256::from_hex(&hex.to_uppercase()), Ok(hash));
+        assert_eq!(
+            Sha256::from_hex(&hex[1..]),
+            Err(HashParseError::InvalidLength(63))
+        );
+        assert_eq!(
+            Sha256::from_bytes(&[0; 31]),
+            Err(HashParseError::InvalidLength(31))
+        );
+        assert!(Sha256::from([1; 32]) < Sha256::from([2; 32]));
+        let set: HashSet<Sha256> = [hash, hash, Sha256::default()].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+}
@@ -5,6 +5,12 @@ use crate::next_multiple_of;
 
 pub const HEADER_MAGIC: [u8; 4] = [0x43, 0x53, 0x47, 0x4d]; // ASCII 'CSGM'
 
+/// File format versions that can be parsed.
+///
+/// Version 2 has the same layout as version 1, but the object map must be sorted by object ID.
+/// Version 3 extends the sorted object map entries with table of contents information.
+pub const SUPPORTED_VERSIONS: [u32; 3] = [1, 2, 3];
+
 #[derive(Debug)]
 pub enum HeaderDecodeError {
     InvalidMagic,
@@ -41,9 +47,9 @@ impl Header {
         Ok(version_bytes)
     }
 
-    pub(crate) fn new(number_of_objects: u64, extra_data: Vec<u8>) -> Self {
+    pub(crate) fn with_version(version: u32, number_of_objects: u64, extra_data: Vec<u8>) -> Self {
         Self {
-            version: 1,
+            version,
             number_of_objects,
             header_len: 0,
             extra_data,
@@ -97,7 +103,7 @@ impl TryFrom<&[u8]> for Header {
         }
 
         let version = Header::partial_version(value)?;
-        if version != 1 {
+        if !SUPPORTED_VERSIONS.contains(&version) {
             return Err(UnsupportedVersion);
         }
 
@@ -160,7 +166,7 @@ mod test {
     #[test]
     pub fn test_header_from_bytes() {
         let valid_header = b"CSGM\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
-        let invalid_version = b"CSGM\x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+        let invalid_version = b"CSGM\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
         let invalid_magic = b"CSBM\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
         let too_short = b"CSGM\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
         let invalid_padding =
@@ -187,20 +193,24 @@ mod test {
 
     #[test]
     pub fn test_to_bytes() {
-        let header = Header::new(1, Vec::new());
+        let header = Header::with_version(1, 1, Vec::new());
         let valid_header_exp = b"CSGM\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
         assert_eq!(Vec::from(header).as_slice(), valid_header_exp);
 
-        let header = Header::new(2, vec![1]);
+        let header = Header::with_version(1, 2, vec![1]);
         let valid_header_exp = b"CSGM\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x20\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
         assert_eq!(Vec::from(header).as_slice(), valid_header_exp);
 
-        let header = Header::new(2, vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        let header = Header::with_version(1, 2, vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
         let valid_header_exp = b"CSGM\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x20\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c";
         assert_eq!(Vec::from(header).as_slice(), valid_header_exp);
 
-        let header = Header::new(2, vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13]);
+        let header = Header::with_version(1, 2, vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13]);
         let valid_header_exp = b"CSGM\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x30\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
         assert_eq!(Vec::from(header).as_slice(), valid_header_exp);
+
+        let header = Header::with_version(2, 1, Vec::new());
+        let valid_header_exp = b"CSGM\x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+        assert_eq!(Vec::from(header).as_slice(), valid_header_exp);
     }
 }
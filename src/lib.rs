@@ -1,7 +1,20 @@
 use num_integer::Integer;
 
+#[cfg(feature = "async")]
+pub mod async_database;
 pub mod database;
 pub mod formats;
+pub mod hash;
+pub mod migration;
+pub mod record;
+pub mod scan;
+pub mod search;
+pub mod shared;
+pub mod source;
+pub mod ssdeep;
+#[cfg(feature = "telfhash")]
+pub mod telfhash;
+pub mod tlsh;
 
 #[cfg(feature = "inspection")]
 pub mod header;
@@ -23,8 +36,13 @@ pub mod raw_database_file;
 #[cfg(not(feature = "inspection"))]
 mod raw_database_file;
 
-pub use database::{Database, LazyLoadedDatabase, LazyParsingError, Object, ObjectImpl};
+pub use database::{
+    Database, FormatError, LazyLoadedDatabase, LazyParsingError, Object, ObjectCompressionType,
+    ObjectFormat, ObjectImpl,
+};
 pub use raw_database_file::DatabaseParseError;
+pub use shared::SharedDatabase;
+pub use source::{DatabaseInfo, DatabaseSource, GetObjectError, ObjectMetadata, SourceError};
 
 pub(crate) fn next_multiple_of<T: Integer + Clone>(lhs: T, rhs: T) -> T {
     lhs.next_multiple_of(&rhs)
@@ -0,0 +1,374 @@
+//! Conversion of objects between formats and entry types.
+//!
+//! A [`MigrationPlan`] is an ordered list of [`Migration`]s, each converting the objects of one
+//! format or entry type to another. [`Database::migrate`](crate::Database::migrate) applies the
+//! plan to a database, so the build pipeline can maintain the newest formats only, and produce
+//! databases for older devices by migrating them back.
+//!
+//! # Example: Upgrade the ColoredTLSH objects of a database
+//! ```rust no_run
+//! use simbiota_database::migration::{Migration, MigrationPlan};
+//! use simbiota_database::Database;
+//!
+//! let file_data = std::fs::read("database_file.sdb").unwrap();
+//! let mut database = Database::from_bytes(file_data.as_slice()).expect("failed to load database");
+//!
+//! let mut plan = MigrationPlan::new();
+//! plan.add_migration(Migration::AddDistance { distance: 40 });
+//! plan.add_object_migration(Migration::SimpleTLSHToRaw, &[0x0001]);
+//! for migrated in database.migrate(&plan).expect("failed to migrate database") {
+//!     println!("migrated object {}", migrated.object_id);
+//! }
+//! std::fs::write("database_file.sdb", database.as_bytes()).unwrap();
+//! ```
+
+use crate::database::{FormatError, Object, ObjectCompressionType, ObjectFormat, ObjectImpl};
+use crate::formats::colored_tlsh::ColoredTLSHObject;
+use crate::formats::colored_tlsh_with_distance::ColoredTLSHWithDistanceObject;
+use crate::formats::simple_tlsh::SimpleTLSHObject;
+use std::collections::HashMap;
+
+/// A conversion of objects from one format or entry type to another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Migration {
+    /// Store the digests of hex SimpleTLSH (0x0001) objects in binary (entry type 1).
+    SimpleTLSHToRaw,
+    /// Store the digests of binary SimpleTLSH (0x0001) objects in hex (entry type 0).
+    SimpleTLSHToHex,
+    /// Convert ColoredTLSH (0x0002) objects to ColoredTLSHWithDistance (0x0003), using the same
+    /// distance threshold for every entry. Labels are kept.
+    AddDistance { distance: u8 },
+    /// Convert ColoredTLSHWithDistance (0x0003) objects to ColoredTLSH (0x0002), dropping the
+    /// distance thresholds. Labels are kept.
+    RemoveDistance,
+    /// Drop the labels of labelled ColoredTLSH and ColoredTLSHWithDistance objects, converting
+    /// them to the unlabelled entry type (0).
+    RemoveLabels,
+}
+
+impl Migration {
+    /// Check whether the migration converts objects with the specified format and entry type.
+    pub fn applies_to(&self, format: u16, entry_type: u16) -> bool {
+        match self {
+            Migration::SimpleTLSHToRaw => format == 0x0001 && entry_type == 0,
+            Migration::SimpleTLSHToHex => format == 0x0001 && entry_type == 1,
+            Migration::AddDistance { .. } => format == 0x0002,
+            Migration::RemoveDistance => format == 0x0003,
+            Migration::RemoveLabels => (format == 0x0002 || format == 0x0003) && entry_type == 1,
+        }
+    }
+
+    /// Convert an object the migration applies to. The converted object keeps the compression
+    /// of the source object.
+    fn apply(&self, obj: &Object) -> Result<Object, FormatError> {
+        let mut converted = match self {
+            Migration::SimpleTLSHToRaw => {
+                let source = SimpleTLSHObject::try_from_object(obj)?;
+                let data = source.get_raw_hashes();
+                simple_tlsh_object(1, data)
+            }
+            Migration::SimpleTLSHToHex => {
+                let source = SimpleTLSHObject::try_from_object(obj)?;
+                let data = source
+                    .get_hashes()
+                    .into_iter()
+                    .map(String::into_bytes)
+                    .collect();
+                simple_tlsh_object(0, data)
+            }
+            Migration::AddDistance { distance } => {
+                let source = ColoredTLSHObject::try_from_object(obj)?;
+                let mut target = ColoredTLSHWithDistanceObject::empty();
+                for e in source.get_entries() {
+                    match e.label {
+                        Some(label) => {
+                            target.add_labelled_entry(e.tlsh, e.sha256_hash, *distance, label)
+                        }
+                        None => target.add_entry(e.tlsh, e.sha256_hash, *distance),
+                    }
+                }
+                target.to_object()
+            }
+            Migration::RemoveDistance => {
+                let source = ColoredTLSHWithDistanceObject::try_from_object(obj)?;
+                let mut target = ColoredTLSHObject::empty();
+                for e in source.get_entries() {
+                    match e.label {
+                        Some(label) => target.add_labelled_entry(e.tlsh, e.sha256_hash, label),
+                        None => target.add_entry(e.tlsh, e.sha256_hash),
+                    }
+                }
+                target.to_object()
+            }
+            Migration::RemoveLabels if obj.format == 0x0002 => {
+                let source = ColoredTLSHObject::try_from_object(obj)?;
+                let mut target = ColoredTLSHObject::empty();
+                for e in source.get_entries() {
+                    target.add_entry(e.tlsh, e.sha256_hash);
+                }
+                target.to_object()
+            }
+            Migration::RemoveLabels => {
+                let source = ColoredTLSHWithDistanceObject::try_from_object(obj)?;
+                let mut target = ColoredTLSHWithDistanceObject::empty();
+                for e in source.get_entries() {
+                    target.add_entry(e.tlsh, e.sha256_hash, e.distance);
+                }
+                target.to_object()
+            }
+        };
+        converted.compression_type = obj.compression_type;
+        Ok(converted)
+    }
+}
+
+/// Uncompressed SimpleTLSH object with the given entries.
+fn simple_tlsh_object(entry_type: u16, data: Vec<Vec<u8>>) -> Object {
+    Object {
+        format: 0x0001,
+        compression_type: ObjectCompressionType::NoCompression,
+        entry_type,
+        entry_size: if entry_type == 0 { 70 } else { 35 },
+        data,
+    }
+}
+
+/// A migration and the objects it converts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStep {
+    pub migration: Migration,
+    /// IDs of the objects to convert. If none, every object the migration applies to is
+    /// converted.
+    pub object_ids: Option<Vec<u64>>,
+}
+
+/// Ordered list of migrations. Each step sees the objects converted by the previous steps.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MigrationPlan {
+    steps: Vec<MigrationStep>,
+}
+
+impl MigrationPlan {
+    pub fn new() -> Self {
+        Self { steps: vec![] }
+    }
+
+    pub fn get_steps(&self) -> &Vec<MigrationStep> {
+        &self.steps
+    }
+
+    /// Convert every object the migration applies to.
+    pub fn add_migration(&mut self, migration: Migration) {
+        self.steps.push(MigrationStep {
+            migration,
+            object_ids: None,
+        });
+    }
+
+    /// Convert the specified objects. The migration fails if any of them is missing or is not in
+    /// a format the migration applies to.
+    pub fn add_object_migration(&mut self, migration: Migration, object_ids: &[u64]) {
+        self.steps.push(MigrationStep {
+            migration,
+            object_ids: Some(object_ids.to_vec()),
+        });
+    }
+}
+
+/// An object converted by a migration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MigratedObject {
+    /// Index of the step in the plan.
+    pub step: usize,
+    pub object_id: u64,
+}
+
+/// Error representing failures that can occur while migrating a database. The database is not
+/// modified if the migration fails.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationError {
+    /// An object listed in the plan is not in the database.
+    NotFound(u64),
+    /// The migration of the step does not apply to an object listed in the plan.
+    NotApplicable { step: usize, object_id: u64 },
+    /// An object to convert is invalid.
+    InvalidObject { object_id: u64, error: FormatError },
+}
+
+/// Apply a migration plan to the objects of a database.
+///
+/// Returns the converted objects and the list of conversions, without modifying the objects.
+pub(crate) fn migrate(
+    objects: &HashMap<u64, Object>,
+    plan: &MigrationPlan,
+) -> Result<(HashMap<u64, Object>, Vec<MigratedObject>), MigrationError> {
+    let mut all_ids: Vec<u64> = objects.keys().copied().collect();
+    all_ids.sort_unstable();
+
+    let mut migrated_objects: HashMap<u64, Object> = HashMap::new();
+    let mut migrated = Vec::new();
+    for (
+        step,
+        MigrationStep {
+            migration,
+            object_ids,
+        },
+    ) in plan.steps.iter().enumerate()
+    {
+        for &object_id in object_ids.as_ref().unwrap_or(&all_ids) {
+            let obj = migrated_objects
+                .get(&object_id)
+                .or_else(|| objects.get(&object_id))
+                .ok_or(MigrationError::NotFound(object_id))?;
+            if !migration.applies_to(obj.format, obj.entry_type) {
+                if object_ids.is_some() {
+                    return Err(MigrationError::NotApplicable { step, object_id });
+                }
+                continue;
+            }
+
+            let converted = migration
+                .apply(obj)
+                .map_err(|error| MigrationError::InvalidObject { object_id, error })?;
+            migrated_objects.insert(object_id, converted);
+            migrated.push(MigratedObject { step, object_id });
+        }
+    }
+    Ok((migrated_objects, migrated))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::database::{FormatError, ObjectCompressionType, ObjectFormat};
+    use crate::formats::colored_tlsh::{ColoredTLSHObject, FamilyLabel};
+    use crate::formats::colored_tlsh_with_distance::ColoredTLSHWithDistanceObject;
+    use crate::formats::simple_tlsh::{SimpleTLSHEntryType, SimpleTLSHObject};
+    use crate::hash::TlshDigest;
+    use crate::migration::{MigratedObject, Migration, MigrationError, MigrationPlan};
+    use crate::{Database, DatabaseSource};
+
+    const LABEL: FamilyLabel = FamilyLabel {
+        string_table_id: 4,
+        index: 7,
+    };
+    const HASH: &str = "B911A8DACB5B5A06568B6ED299B18014C811DD897E95B720B871B1F5EF7300538187DC";
+
+    fn generate_database() -> Database {
+        let mut database = Database::new(1);
+        let mut tlsh_object = SimpleTLSHObject::new(SimpleTLSHEntryType::HEX);
+        tlsh_object.add_hash(HASH.to_string());
+        database.put(1, tlsh_object);
+        let mut colored = ColoredTLSHObject::empty();
+        colored.add_entry([1; 36], [2; 32]);
+        colored.add_labelled_entry([3; 36], [4; 32], LABEL);
+        database.put(2, colored);
+        let mut with_distance = ColoredTLSHWithDistanceObject::empty();
+        with_distance.add_entry([5; 36], [6; 32], 30);
+        database.put(3, with_distance);
+        database
+    }
+
+    #[test]
+    pub fn test_migrate() {
+        let mut database = generate_database();
+        for id in [1, 3] {
+            database.get_object_mut(id).unwrap().compression_type = ObjectCompressionType::DEFLATE;
+        }
+        let mut plan = MigrationPlan::new();
+        plan.add_migration(Migration::SimpleTLSHToRaw);
+        plan.add_migration(Migration::RemoveDistance);
+        plan.add_object_migration(Migration::AddDistance { distance: 40 }, &[2, 3]);
+        let migrated = database.migrate(&plan).unwrap();
+        let steps: Vec<(usize, u64)> = migrated.iter().map(|m| (m.step, m.object_id)).collect();
+        assert_eq!(steps, vec![(0, 1), (1, 3), (2, 2), (2, 3)]);
+
+        let tlsh_object = database.get_object(1).unwrap();
+        assert_eq!((tlsh_object.entry_type, tlsh_object.entry_size), (1, 35));
+        assert_eq!(tlsh_object.compression_type, ObjectCompressionType::DEFLATE);
+        let tlsh_object: SimpleTLSHObject = database.get(1).unwrap();
+        assert_eq!(tlsh_object.get_hashes(), vec![HASH.to_string()]);
+
+        let colored: ColoredTLSHWithDistanceObject = database.get(2).unwrap();
+        let entries: Vec<(u8, Option<FamilyLabel>)> = colored
+            .get_entries()
+            .iter()
+            .map(|e| (e.distance, e.label))
+            .collect();
+        assert_eq!(entries, vec![(40, None), (40, Some(LABEL))]);
+        assert_eq!(
+            database.get_object(2).unwrap().compression_type,
+            ObjectCompressionType::NoCompression
+        );
+        assert_eq!(
+            database.get_object(3).unwrap().compression_type,
+            ObjectCompressionType::DEFLATE
+        );
+        let with_distance: ColoredTLSHWithDistanceObject = database.get(3).unwrap();
+        assert_eq!(with_distance.get_entries()[0].distance, 40);
+        assert_eq!(
+            with_distance.get_entries()[0].tlsh,
+            TlshDigest::from([5; 36])
+        );
+
+        // Back to the original formats
+        let mut plan = MigrationPlan::new();
+        plan.add_migration(Migration::SimpleTLSHToHex);
+        plan.add_migration(Migration::RemoveLabels);
+        plan.add_object_migration(Migration::RemoveDistance, &[2]);
+        assert_eq!(
+            database.migrate(&plan).unwrap(),
+            vec![
+                MigratedObject {
+                    step: 0,
+                    object_id: 1
+                },
+                MigratedObject {
+                    step: 1,
+                    object_id: 2
+                },
+                MigratedObject {
+                    step: 2,
+                    object_id: 2
+                },
+            ]
+        );
+        let tlsh_object = SimpleTLSHObject::try_from_object(database.get_object(1).unwrap());
+        assert_eq!(tlsh_object.unwrap().get_hashes(), vec![HASH.to_string()]);
+        let colored = database.get_object(2).unwrap();
+        assert_eq!((colored.format, colored.entry_type), (0x0002, 0));
+        let colored = ColoredTLSHObject::try_from_object(colored).unwrap();
+        assert!(colored.get_entries().iter().all(|e| e.label.is_none()));
+    }
+
+    #[test]
+    pub fn test_failed_migration() {
+        let mut database = generate_database();
+        let mut plan = MigrationPlan::new();
+        plan.add_migration(Migration::SimpleTLSHToRaw);
+        plan.add_object_migration(Migration::RemoveDistance, &[2]);
+        assert_eq!(
+            database.migrate(&plan),
+            Err(MigrationError::NotApplicable {
+                step: 1,
+                object_id: 2
+            })
+        );
+        // Nothing is modified
+        assert_eq!(database.get_object(1).unwrap().entry_type, 0);
+
+        let mut plan = MigrationPlan::new();
+        plan.add_object_migration(Migration::RemoveLabels, &[4]);
+        assert_eq!(database.migrate(&plan), Err(MigrationError::NotFound(4)));
+
+        database.get_object_mut(1).unwrap().data[0][0] = b'X';
+        let mut plan = MigrationPlan::new();
+        plan.add_migration(Migration::SimpleTLSHToRaw);
+        assert_eq!(
+            database.migrate(&plan),
+            Err(MigrationError::InvalidObject {
+                object_id: 1,
+                error: FormatError::MalformedEntry(0)
+            })
+        );
+    }
+}
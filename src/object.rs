@@ -1,10 +1,20 @@
+use crate::database::VARIABLE_ENTRY_SIZE;
 use crate::next_multiple_of;
-use crate::object::ObjectDecodeError::{CompressionError, TooShort, UnsupportedCompression};
+#[cfg(feature = "compression")]
+use crate::object::ObjectDecodeError::CompressionError;
+use crate::object::ObjectDecodeError::{
+    IOError, InvalidEntryTable, InvalidPadding, TooShort, UnsupportedCompression,
+};
 
 #[cfg(feature = "compression")]
 use flate2::read::ZlibDecoder;
 #[cfg(feature = "compression")]
+use flate2::write::ZlibEncoder;
+#[cfg(feature = "compression")]
+use flate2::Compression;
 use std::io::Read;
+#[cfg(feature = "compression")]
+use std::io::Write;
 
 #[derive(Debug)]
 pub enum ObjectDecodeError {
@@ -12,6 +22,10 @@ pub enum ObjectDecodeError {
     InvalidPadding,
     UnsupportedCompression(u16),
     CompressionError(std::io::Error),
+    IOError(std::io::Error),
+    /// The offset table of a variable-length object is truncated or the offsets are out of
+    /// range.
+    InvalidEntryTable,
 }
 
 #[derive(Debug)]
@@ -36,23 +50,163 @@ impl RawObject {
         }
     }
 
-    fn decode_data(compression: u16, input_data: &[u8]) -> Result<Vec<u8>, ObjectDecodeError> {
-        match compression {
-            0x0000 => Ok(input_data.to_vec()),
-            0x0001 => {
-                // flate2 deflate
-                if cfg!(feature = "compression") {
-                    Self::decode_flate2(input_data)
-                } else {
-                    Err(UnsupportedCompression(0x0001))
+    /// Append a new entry to the object.
+    pub(crate) fn add_data(&mut self, entry: Vec<u8>) {
+        self.data.push(entry);
+    }
+
+    /// Reads an object from a stream. Compressed entries are decompressed while reading, so the
+    /// compressed data is never kept in memory as a whole.
+    ///
+    /// Only the object itself is consumed from the reader, the padding is not.
+    pub(crate) fn read_from<R: Read>(mut reader: R) -> Result<Self, ObjectDecodeError> {
+        let mut header = [0u8; 16];
+        reader.read_exact(&mut header).map_err(IOError)?;
+
+        let mut object = Self::parse_header(&header);
+        if object.length <= 16 {
+            return Err(TooShort);
+        }
+        let data_length = object.length - (2 + 2 + 2 + 2 + 8);
+        let mut body = reader.take(data_length);
+        let decoded_data = match object.compression {
+            0x0000 => {
+                // The length comes from the file, so the buffer only grows with the data read
+                let mut data = Vec::new();
+                body.read_to_end(&mut data).map_err(IOError)?;
+                if data.len() as u64 != data_length {
+                    return Err(TooShort);
                 }
+                data
+            }
+            c => Self::decode_data(c, body)?,
+        };
+        object.data = Self::split_entries(&decoded_data, object.entry_size)?;
+        Ok(object)
+    }
+
+    /// Creates an empty object from the 16 byte object header.
+    pub(crate) fn parse_header(value: &[u8]) -> Self {
+        let format = u16::from_be_bytes((&value[0..2]).try_into().unwrap());
+        let compression = u16::from_be_bytes((&value[2..4]).try_into().unwrap());
+        let entry_type = u16::from_be_bytes((&value[4..6]).try_into().unwrap());
+        let entry_size = u16::from_be_bytes((&value[6..8]).try_into().unwrap());
+        let length = u64::from_be_bytes((&value[8..16]).try_into().unwrap());
+        Self {
+            format,
+            compression,
+            entry_type,
+            entry_size,
+            length,
+            data: Vec::new(),
+        }
+    }
+
+    fn split_entries(
+        decoded_data: &[u8],
+        entry_size: u16,
+    ) -> Result<Vec<Vec<u8>>, ObjectDecodeError> {
+        if entry_size == VARIABLE_ENTRY_SIZE {
+            return Self::split_variable_entries(decoded_data);
+        }
+        Ok(decoded_data
+            .chunks_exact(entry_size as usize)
+            .map(|c| c.to_vec())
+            .collect())
+    }
+
+    /// Splits variable-length entries: an entry count, the end offset of each entry relative to
+    /// the data region, then the data region itself.
+    fn split_variable_entries(decoded_data: &[u8]) -> Result<Vec<Vec<u8>>, ObjectDecodeError> {
+        let read_u64 = |offset: usize| -> Option<u64> {
+            let bytes = decoded_data.get(offset..offset + 8)?;
+            Some(u64::from_be_bytes(bytes.try_into().unwrap()))
+        };
+        let entry_count = read_u64(0).ok_or(InvalidEntryTable)?;
+        let table_end = entry_count
+            .checked_add(1)
+            .and_then(|n| n.checked_mul(8))
+            .filter(|end| *end <= decoded_data.len() as u64)
+            .ok_or(InvalidEntryTable)? as usize;
+        let entry_data = &decoded_data[table_end..];
+
+        let mut entries = Vec::with_capacity(entry_count as usize);
+        let mut start = 0u64;
+        for i in 0..entry_count as usize {
+            let end = read_u64(8 + i * 8).unwrap();
+            if end < start || end > entry_data.len() as u64 {
+                return Err(InvalidEntryTable);
             }
+            entries.push(entry_data[start as usize..end as usize].to_vec());
+            start = end;
+        }
+        Ok(entries)
+    }
+
+    /// Lays out the entries for encoding. Fixed size entries are tightly packed, variable-length
+    /// entries are preceded by their offset table.
+    ///
+    /// Panics if a fixed size entry has the wrong length.
+    fn join_entries(entries: &[Vec<u8>], entry_size: u16) -> Vec<u8> {
+        if entry_size == VARIABLE_ENTRY_SIZE {
+            let data_length: usize = entries.iter().map(|e| e.len()).sum();
+            let mut entry_data = Vec::with_capacity(8 + entries.len() * 8 + data_length);
+            entry_data.extend_from_slice(&(entries.len() as u64).to_be_bytes());
+            let mut end = 0u64;
+            for entry in entries {
+                end += entry.len() as u64;
+                entry_data.extend_from_slice(&end.to_be_bytes());
+            }
+            entries.iter().for_each(|e| entry_data.extend_from_slice(e));
+            return entry_data;
+        }
+
+        let mut entry_data = Vec::with_capacity(entries.len() * entry_size as usize);
+        for entry in entries {
+            assert_eq!(entry.len(), entry_size as usize);
+            entry.iter().for_each(|b| entry_data.push(*b));
+        }
+        entry_data
+    }
+
+    fn decode_data<R: Read>(
+        compression: u16,
+        mut input_data: R,
+    ) -> Result<Vec<u8>, ObjectDecodeError> {
+        match compression {
+            0x0000 => {
+                let mut data = Vec::new();
+                input_data.read_to_end(&mut data).map_err(IOError)?;
+                Ok(data)
+            }
+            // flate2 deflate
+            #[cfg(feature = "compression")]
+            0x0001 => Self::decode_flate2(input_data),
             c => Err(UnsupportedCompression(c)),
         }
     }
 
+    /// Compresses the tightly packed entries with the specified compression.
+    ///
+    /// Panics if the compression is not supported.
+    fn encode_data(compression: u16, entry_data: Vec<u8>) -> Vec<u8> {
+        match compression {
+            0x0000 => entry_data,
+            // flate2 deflate
+            #[cfg(feature = "compression")]
+            0x0001 => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder
+                    .write_all(&entry_data)
+                    .expect("in-memory compression failed");
+                encoder.finish().expect("in-memory compression failed")
+            }
+            c => panic!("unsupported compression: {:#x}", c),
+        }
+    }
+
     #[cfg(feature = "compression")]
-    fn decode_flate2(input_data: &[u8]) -> Result<Vec<u8>, ObjectDecodeError> {
+    fn decode_flate2<R: Read>(input_data: R) -> Result<Vec<u8>, ObjectDecodeError> {
         let mut decoder = ZlibDecoder::new(input_data);
         let mut decoded = Vec::new();
         decoder
@@ -79,11 +233,8 @@ impl TryFrom<&[u8]> for RawObject {
             return Err(TooShort);
         }
 
-        let format = u16::from_be_bytes((&value[0..2]).try_into().unwrap());
-        let compression = u16::from_be_bytes((&value[2..4]).try_into().unwrap());
-        let entry_type = u16::from_be_bytes((&value[4..6]).try_into().unwrap());
-        let entry_size = u16::from_be_bytes((&value[6..8]).try_into().unwrap());
-        let length = u64::from_be_bytes((&value[8..16]).try_into().unwrap());
+        let mut object = Self::parse_header(value);
+        let length = object.length;
 
         if data_length < length as usize {
             return Err(TooShort);
@@ -91,21 +242,21 @@ impl TryFrom<&[u8]> for RawObject {
         if length <= 16 {
             return Err(TooShort);
         }
+        // Padding bytes, if present in the input, must be zero
+        let padded_length = next_multiple_of(length as usize, 16).min(data_length);
+        if value[length as usize..padded_length]
+            .iter()
+            .any(|b| *b != 0)
+        {
+            return Err(InvalidPadding);
+        }
+
         let data_length = length - (2 + 2 + 2 + 2 + 8);
-        let decoded_data = Self::decode_data(compression, &value[16..(16 + data_length) as usize])?;
-        let data: Vec<Vec<u8>> = decoded_data
-            .chunks_exact(entry_size as usize)
-            .map(|c| c.to_vec())
-            .collect();
+        let decoded_data =
+            Self::decode_data(object.compression, &value[16..(16 + data_length) as usize])?;
+        object.data = Self::split_entries(&decoded_data, object.entry_size)?;
 
-        Ok(Self {
-            format,
-            compression,
-            entry_size,
-            entry_type,
-            length,
-            data,
-        })
+        Ok(object)
     }
 }
 
@@ -136,20 +287,17 @@ impl From<RawObject> for Vec<u8> {
             .to_be_bytes()
             .iter()
             .for_each(|v| data.push(*v));
-        let entry_count = value.data.len();
-        let raw_length = 16 + (entry_count * value.entry_size as usize);
+        let entry_data = RawObject::join_entries(&value.data, value.entry_size);
+        let entry_data = RawObject::encode_data(value.compression, entry_data);
+
+        let raw_length = 16 + entry_data.len();
         let full_length = next_multiple_of(raw_length, 16);
         (raw_length as u64)
             .to_be_bytes()
             .iter()
             .for_each(|b| data.push(*b));
         let padding_len = full_length - raw_length;
-        // TODO: Compress
-        assert_eq!(value.compression, 0);
-        for entry in &value.data {
-            assert_eq!(entry.len(), value.entry_size as usize);
-            entry.iter().for_each(|b| data.push(*b));
-        }
+        data.extend_from_slice(&entry_data);
 
         // Add padding
         (0..padding_len).for_each(|_| data.push(0));
@@ -182,6 +330,98 @@ mod test {
         assert!(matches!(object, ObjectDecodeError::InvalidPadding));
     }
 
+    #[cfg(feature = "compression")]
+    #[test]
+    pub fn test_compressed_object() {
+        let mut object = RawObject::new(0x01, 0x01, 0x01, 0x10);
+        object.add_data(vec![0xaa; 16]);
+        object.add_data(vec![0x55; 16]);
+        let data = Vec::from(object);
+        assert_eq!(data.len() % 16, 0);
+
+        let decoded = RawObject::try_from(data.as_slice()).unwrap();
+        assert_eq!(decoded.compression, 0x01);
+        assert_eq!(decoded.data, vec![vec![0xaa; 16], vec![0x55; 16]]);
+
+        let streamed = RawObject::read_from(data.as_slice()).unwrap();
+        assert_eq!(streamed.length, decoded.length);
+        assert_eq!(streamed.data, decoded.data);
+    }
+
+    #[test]
+    pub fn test_variable_object() {
+        let mut object = RawObject::new(0x01, 0x00, 0x00, 0x00);
+        object.add_data(b"first".to_vec());
+        object.add_data(vec![]);
+        object.add_data(b"third entry".to_vec());
+        let data = Vec::from(object);
+        assert_eq!(data.len() % 16, 0);
+        // Entry count and the end offset of each entry
+        assert_eq!(&data[16..24], &3u64.to_be_bytes());
+        assert_eq!(&data[24..32], &5u64.to_be_bytes());
+        assert_eq!(&data[32..40], &5u64.to_be_bytes());
+        assert_eq!(&data[40..48], &16u64.to_be_bytes());
+        assert_eq!(&data[48..64], b"firstthird entry");
+
+        let decoded = RawObject::try_from(data.as_slice()).unwrap();
+        assert_eq!(decoded.entry_size, 0);
+        assert_eq!(
+            decoded.data,
+            vec![b"first".to_vec(), vec![], b"third entry".to_vec()]
+        );
+        let streamed = RawObject::read_from(data.as_slice()).unwrap();
+        assert_eq!(streamed.data, decoded.data);
+
+        let empty = Vec::from(RawObject::new(0x01, 0x00, 0x00, 0x00));
+        assert!(RawObject::try_from(empty.as_slice())
+            .unwrap()
+            .data
+            .is_empty());
+    }
+
+    #[test]
+    pub fn test_invalid_entry_table() {
+        let mut object = RawObject::new(0x01, 0x00, 0x00, 0x00);
+        object.add_data(b"entry".to_vec());
+        let data = Vec::from(object);
+
+        // End offset past the data region
+        let mut invalid_offset = data.clone();
+        invalid_offset[31] = 6;
+        let err = RawObject::try_from(invalid_offset.as_slice()).unwrap_err();
+        assert!(matches!(err, ObjectDecodeError::InvalidEntryTable));
+
+        // Entry count larger than the table
+        let mut invalid_count = data.clone();
+        invalid_count[23] = 4;
+        let err = RawObject::try_from(invalid_count.as_slice()).unwrap_err();
+        assert!(matches!(err, ObjectDecodeError::InvalidEntryTable));
+    }
+
+    #[test]
+    pub fn test_oversized_length() {
+        let mut object = RawObject::new(0x01, 0x00, 0x00, 0x00);
+        object.add_data(b"entry".to_vec());
+        let mut data = Vec::from(object);
+        data[8..16].copy_from_slice(&(u64::MAX / 2).to_be_bytes());
+        let err = RawObject::read_from(data.as_slice()).unwrap_err();
+        assert!(matches!(err, ObjectDecodeError::TooShort));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    pub fn test_compressed_variable_object() {
+        let entries = vec![b"a".to_vec(), vec![0x55; 100], b"abc".to_vec()];
+        let mut object = RawObject::new(0x01, 0x01, 0x00, 0x00);
+        entries.iter().for_each(|e| object.add_data(e.clone()));
+        let data = Vec::from(object);
+
+        let decoded = RawObject::try_from(data.as_slice()).unwrap();
+        assert_eq!(decoded.data, entries);
+        let streamed = RawObject::read_from(data.as_slice()).unwrap();
+        assert_eq!(streamed.data, entries);
+    }
+
     #[test]
     pub fn test_object_save() {
         let data_raw = b"\x00\x01\x00\x00\x00\x01\x00\x10\x00\x00\x00\x00\x00\x00\x00\x30\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02";
@@ -190,7 +430,7 @@ mod test {
         object1.add_data(vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
         assert_eq!(Vec::from(object1).as_slice(), data_raw);
 
-        let data_raw_padded = b"\x00\x01\x00\x00\x00\x01\x00\x06\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x02\x00\x00\x00\x00";
+        let data_raw_padded = b"\x00\x01\x00\x00\x00\x01\x00\x06\x00\x00\x00\x00\x00\x00\x00\x1c\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x02\x00\x00\x00\x00";
         let mut object2 = RawObject::new(0x01, 0x00, 0x01, 0x6);
         object2.add_data(vec![0, 0, 0, 0, 0, 1]);
         object2.add_data(vec![0, 0, 0, 0, 0, 2]);
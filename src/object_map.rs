@@ -1,28 +1,74 @@
-use crate::object_map::ObjectMappingError::{InvalidLength, InvalidPadding};
+use crate::next_multiple_of;
+use crate::object_map::ObjectMappingError::{InvalidLength, InvalidPadding, NotSorted};
+use std::collections::HashMap;
+
+/// Size of an object map entry in version 1 and 2 files.
+pub const MAPPING_SIZE_V1: usize = 16;
+/// Size of an object map entry in version 3 files, including the table of contents fields.
+pub const MAPPING_SIZE_V3: usize = 48;
 
 #[derive(Debug)]
 pub enum ObjectMappingError {
     InvalidLength,
     InvalidPadding,
+    /// The object map was expected to be sorted by object ID, but it was not.
+    NotSorted,
+}
+
+/// Table of contents information of an object, stored in version 3 object maps.
+///
+/// Contains the object header fields and the number of entries, so objects can be inspected
+/// and planned for reading without accessing the object data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectMappingInfo {
+    pub format: u16,
+    pub compression: u16,
+    pub entry_type: u16,
+    pub entry_size: u16,
+    /// Stored length of the object (header + entries) without the padding.
+    pub length: u64,
+    pub entry_count: u64,
+}
+
+impl ObjectMappingInfo {
+    /// Number of bytes the object occupies in the file, including the padding.
+    pub fn padded_length(&self) -> u64 {
+        next_multiple_of(self.length, 16)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ObjectMapping {
     pub id: u64,
     pub offset: u64,
+    /// Table of contents information, only available in version 3 files.
+    pub info: Option<ObjectMappingInfo>,
 }
 
 impl ObjectMapping {
     pub fn new(id: u64, offset: u64) -> Self {
-        Self { id, offset }
+        Self {
+            id,
+            offset,
+            info: None,
+        }
+    }
+
+    pub fn with_info(id: u64, offset: u64, info: ObjectMappingInfo) -> Self {
+        Self {
+            id,
+            offset,
+            info: Some(info),
+        }
     }
 }
 
 impl TryFrom<&[u8]> for ObjectMapping {
     type Error = ObjectMappingError;
 
+    /// Parses a 16 byte (version 1 and 2) or 48 byte (version 3) mapping.
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        if value.len() != 16 {
+        if value.len() != MAPPING_SIZE_V1 && value.len() != MAPPING_SIZE_V3 {
             return Err(InvalidLength);
         }
 
@@ -31,43 +77,182 @@ impl TryFrom<&[u8]> for ObjectMapping {
         if offset % 16 != 0 {
             return Err(InvalidPadding);
         }
+        if value.len() == MAPPING_SIZE_V1 {
+            return Ok(Self::new(id, offset));
+        }
+
+        let info = ObjectMappingInfo {
+            format: u16::from_be_bytes((&value[16..18]).try_into().unwrap()),
+            compression: u16::from_be_bytes((&value[18..20]).try_into().unwrap()),
+            entry_type: u16::from_be_bytes((&value[20..22]).try_into().unwrap()),
+            entry_size: u16::from_be_bytes((&value[22..24]).try_into().unwrap()),
+            length: u64::from_be_bytes((&value[24..32]).try_into().unwrap()),
+            entry_count: u64::from_be_bytes((&value[32..40]).try_into().unwrap()),
+        };
+        // 8 reserved bytes, must be zero
+        if value[40..48].iter().any(|b| *b != 0) {
+            return Err(InvalidPadding);
+        }
 
-        Ok(Self { id, offset })
+        Ok(Self::with_info(id, offset, info))
     }
 }
 
+/// Object map of a database file, with an index for finding objects by their ID.
 #[derive(Debug)]
 pub struct ObjectMap {
-    pub mappings: Vec<ObjectMapping>,
+    mappings: Vec<ObjectMapping>,
+    index: HashMap<u64, usize>,
 }
 
 impl ObjectMap {
     pub(crate) fn new() -> Self {
         Self {
             mappings: Vec::new(),
+            index: HashMap::new(),
         }
     }
 
     pub(crate) fn try_from(value: &[u8], entry_count: u64) -> Result<Self, ObjectMappingError> {
+        Self::parse(value, entry_count, MAPPING_SIZE_V1)
+    }
+
+    /// Parses the object map of a file with the specified format version. From version 2, the
+    /// object map must be sorted by object ID.
+    pub(crate) fn try_from_version(
+        value: &[u8],
+        entry_count: u64,
+        version: u32,
+    ) -> Result<Self, ObjectMappingError> {
+        let object_map = if version >= 3 {
+            Self::parse(value, entry_count, MAPPING_SIZE_V3)?
+        } else {
+            Self::try_from(value, entry_count)?
+        };
+        if version >= 2 && !object_map.is_sorted() {
+            return Err(NotSorted);
+        }
+        Ok(object_map)
+    }
+
+    fn parse(
+        value: &[u8],
+        entry_count: u64,
+        mapping_size: usize,
+    ) -> Result<Self, ObjectMappingError> {
         let data_len = value.len();
         if data_len == 0 {
             return Err(InvalidLength);
         }
 
-        if data_len < (entry_count * 16) as usize {
+        let map_len = entry_count
+            .checked_mul(mapping_size as u64)
+            .ok_or(InvalidLength)?;
+        if (data_len as u64) < map_len {
             return Err(InvalidLength);
         }
 
         let num_of_entries = entry_count;
 
-        let mut entries = Vec::new();
+        let mut object_map = Self::new();
         for index in 0..num_of_entries {
-            let offset = (index * 16) as usize;
-            let mapping = ObjectMapping::try_from(&value[offset..offset + 16])?;
-            entries.push(mapping);
+            let offset = index as usize * mapping_size;
+            let mapping = ObjectMapping::try_from(&value[offset..offset + mapping_size])?;
+            object_map.push(mapping);
         }
 
-        Ok(Self { mappings: entries })
+        Ok(object_map)
+    }
+
+    /// Size of a single mapping in a file with the specified format version.
+    pub fn mapping_size(version: u32) -> usize {
+        if version >= 3 {
+            MAPPING_SIZE_V3
+        } else {
+            MAPPING_SIZE_V1
+        }
+    }
+
+    /// Length of the object map of a file, from the version and object count in its header.
+    ///
+    /// Fails if the map would not fit in the file after the header, so a corrupt header is
+    /// rejected before the map is allocated.
+    pub(crate) fn checked_length(
+        version: u32,
+        number_of_objects: u64,
+        header_len: u32,
+        file_len: u64,
+    ) -> Result<usize, ObjectMappingError> {
+        (Self::mapping_size(version) as u64)
+            .checked_mul(number_of_objects)
+            .filter(|length| {
+                length
+                    .checked_add(header_len as u64)
+                    .is_some_and(|end| end <= file_len)
+            })
+            .map(|length| length as usize)
+            .ok_or(InvalidLength)
+    }
+
+    /// Add a new mapping to the end of the map.
+    ///
+    /// If the map already contains the ID, lookups will keep returning the first mapping.
+    pub(crate) fn push(&mut self, mapping: ObjectMapping) {
+        self.index.entry(mapping.id).or_insert(self.mappings.len());
+        self.mappings.push(mapping);
+    }
+
+    /// Check whether the mappings are sorted by object ID.
+    pub fn is_sorted(&self) -> bool {
+        self.mappings.windows(2).all(|w| w[0].id <= w[1].id)
+    }
+
+    /// Every mapping in the order they are stored in the file.
+    pub fn mappings(&self) -> &[ObjectMapping] {
+        &self.mappings
+    }
+
+    /// Get the mapping of the specified object.
+    pub fn get(&self, id: u64) -> Option<&ObjectMapping> {
+        self.index
+            .get(&id)
+            .map(|position| &self.mappings[*position])
+    }
+
+    /// Check if the map contains the specified object.
+    pub fn contains(&self, id: u64) -> bool {
+        self.index.contains_key(&id)
+    }
+
+    /// Binary search in a sorted object map without loading it into memory.
+    ///
+    /// `read_mapping` is called with the index of the mapping to read, at most
+    /// `log2(entry_count) + 1` times.
+    pub(crate) fn search_sorted<E>(
+        entry_count: u64,
+        id: u64,
+        mut read_mapping: impl FnMut(u64) -> Result<ObjectMapping, E>,
+    ) -> Result<Option<ObjectMapping>, E> {
+        let mut low = 0;
+        let mut high = entry_count;
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let mapping = read_mapping(middle)?;
+            if mapping.id < id {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        if low == entry_count {
+            return Ok(None);
+        }
+        let mapping = read_mapping(low)?;
+        Ok(if mapping.id == id {
+            Some(mapping)
+        } else {
+            None
+        })
     }
 }
 
@@ -78,6 +263,28 @@ impl From<ObjectMap> for Vec<u8> {
         for obj in &value.mappings {
             obj.id.to_be_bytes().iter().for_each(|v| data.push(*v));
             obj.offset.to_be_bytes().iter().for_each(|v| data.push(*v));
+            if let Some(info) = &obj.info {
+                info.format.to_be_bytes().iter().for_each(|v| data.push(*v));
+                info.compression
+                    .to_be_bytes()
+                    .iter()
+                    .for_each(|v| data.push(*v));
+                info.entry_type
+                    .to_be_bytes()
+                    .iter()
+                    .for_each(|v| data.push(*v));
+                info.entry_size
+                    .to_be_bytes()
+                    .iter()
+                    .for_each(|v| data.push(*v));
+                info.length.to_be_bytes().iter().for_each(|v| data.push(*v));
+                info.entry_count
+                    .to_be_bytes()
+                    .iter()
+                    .for_each(|v| data.push(*v));
+                // Reserved
+                (0..8).for_each(|_| data.push(0));
+            }
         }
 
         data
@@ -86,33 +293,113 @@ impl From<ObjectMap> for Vec<u8> {
 
 #[cfg(test)]
 mod test {
-    use crate::object_map::{ObjectMap, ObjectMapping, ObjectMappingError};
+    use crate::object_map::{ObjectMap, ObjectMapping, ObjectMappingError, ObjectMappingInfo};
 
     #[test]
     pub fn test_mapping_load() {
-        let raw_data = b"\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x55\xaa";
+        let raw_data = b"\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x55\xa0";
         let invalid_len = b"\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x55";
         let mapping = ObjectMap::try_from(raw_data as &[u8], 2).unwrap();
-        assert_eq!(mapping.mappings.len(), 2);
-        assert_eq!(mapping.mappings[0].id, 1);
-        assert_eq!(mapping.mappings[1].id, 32);
-        assert_eq!(mapping.mappings[0].offset, 32);
-        assert_eq!(mapping.mappings[1].offset, 0x55aa);
+        assert_eq!(mapping.mappings().len(), 2);
+        assert_eq!(mapping.mappings()[0].id, 1);
+        assert_eq!(mapping.mappings()[1].id, 32);
+        assert_eq!(mapping.mappings()[0].offset, 32);
+        assert_eq!(mapping.mappings()[1].offset, 0x55a0);
 
         let invalid_err = ObjectMap::try_from(invalid_len as &[u8], 2).unwrap_err();
         assert!(matches!(invalid_err, ObjectMappingError::InvalidLength));
+
+        let overflow_err = ObjectMap::try_from(raw_data as &[u8], u64::MAX / 8).unwrap_err();
+        assert!(matches!(overflow_err, ObjectMappingError::InvalidLength));
+    }
+
+    #[test]
+    pub fn test_checked_length() {
+        assert_eq!(ObjectMap::checked_length(1, 2, 32, 64).unwrap(), 32);
+        assert_eq!(ObjectMap::checked_length(3, 2, 32, 128).unwrap(), 96);
+        for (version, number_of_objects, file_len) in
+            [(1, 3, 64), (1, 1 << 34, 48), (3, u64::MAX, u64::MAX)]
+        {
+            let err = ObjectMap::checked_length(version, number_of_objects, 32, file_len);
+            assert!(matches!(err, Err(ObjectMappingError::InvalidLength)));
+        }
     }
 
     #[test]
     pub fn test_mapping_store() {
         let raw_data = b"\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x55\xaa";
         let mut mapping = ObjectMap::new();
-        mapping.mappings.push(ObjectMapping { id: 1, offset: 32 });
-        mapping.mappings.push(ObjectMapping {
-            id: 32,
-            offset: 0x55aa,
-        });
+        mapping.push(ObjectMapping::new(1, 32));
+        mapping.push(ObjectMapping::new(32, 0x55aa));
 
         assert_eq!(Vec::from(mapping).as_slice(), raw_data);
     }
+
+    #[test]
+    pub fn test_mapping_index() {
+        let mut mapping = ObjectMap::new();
+        mapping.push(ObjectMapping::new(5, 0x40));
+        mapping.push(ObjectMapping::new(2, 0x80));
+        mapping.push(ObjectMapping::new(5, 0xc0));
+        assert!(!mapping.is_sorted());
+        assert_eq!(mapping.get(5).unwrap().offset, 0x40);
+        assert_eq!(mapping.get(2).unwrap().offset, 0x80);
+        assert!(mapping.get(3).is_none());
+        assert!(mapping.contains(2));
+
+        let unsorted = b"\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x30";
+        ObjectMap::try_from_version(unsorted as &[u8], 2, 1).unwrap();
+        let sorted_err = ObjectMap::try_from_version(unsorted as &[u8], 2, 2).unwrap_err();
+        assert!(matches!(sorted_err, ObjectMappingError::NotSorted));
+    }
+
+    #[test]
+    pub fn test_search_sorted() {
+        let mappings: Vec<ObjectMapping> = [1, 3, 3, 8, 20]
+            .iter()
+            .enumerate()
+            .map(|(i, id)| ObjectMapping::new(*id, i as u64 * 16))
+            .collect();
+        let mut reads = 0;
+        let mut read = |index: u64| -> Result<ObjectMapping, ()> {
+            reads += 1;
+            Ok(mappings[index as usize].clone())
+        };
+
+        let found = ObjectMap::search_sorted(5, 8, &mut read).unwrap().unwrap();
+        assert_eq!(found.offset, 48);
+        let first_duplicate = ObjectMap::search_sorted(5, 3, &mut read).unwrap().unwrap();
+        assert_eq!(first_duplicate.offset, 16);
+        assert!(ObjectMap::search_sorted(5, 0, &mut read).unwrap().is_none());
+        assert!(ObjectMap::search_sorted(5, 21, &mut read)
+            .unwrap()
+            .is_none());
+        assert!(ObjectMap::search_sorted(5, 4, &mut read).unwrap().is_none());
+        assert!(ObjectMap::search_sorted(0, 4, &mut read).unwrap().is_none());
+        assert!(reads <= 5 * 4);
+    }
+
+    #[test]
+    pub fn test_mapping_info() {
+        let raw_data = b"\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x50\x00\x03\x00\x01\x00\x00\x00\x45\x00\x00\x00\x00\x00\x00\x01\x23\x00\x00\x00\x00\x00\x00\x00\x0a\x00\x00\x00\x00\x00\x00\x00\x00";
+        let info = ObjectMappingInfo {
+            format: 3,
+            compression: 1,
+            entry_type: 0,
+            entry_size: 0x45,
+            length: 0x123,
+            entry_count: 10,
+        };
+        let mut mapping = ObjectMap::new();
+        mapping.push(ObjectMapping::with_info(1, 0x50, info.clone()));
+        assert_eq!(Vec::from(mapping).as_slice(), raw_data);
+
+        let mapping = ObjectMap::try_from_version(raw_data as &[u8], 1, 3).unwrap();
+        assert_eq!(mapping.get(1).unwrap().offset, 0x50);
+        assert_eq!(mapping.get(1).unwrap().info, Some(info.clone()));
+        assert_eq!(info.padded_length(), 0x130);
+
+        let v2_err = ObjectMap::try_from_version(raw_data as &[u8], 2, 2).unwrap_err();
+        assert!(matches!(v2_err, ObjectMappingError::InvalidPadding));
+    }
 }
@@ -2,7 +2,8 @@ use crate::header::{Header, HeaderDecodeError};
 use crate::object::{ObjectDecodeError, RawObject};
 use crate::object_map::{ObjectMap, ObjectMapping, ObjectMappingError};
 use crate::raw_database_file::DatabaseParseError::{
-    InvalidHeader, InvalidObject, InvalidObjectMap, InvalidObjectOffset, UnsupportedVersion,
+    InvalidHeader, InvalidObject, InvalidObjectMap, InvalidObjectOffset, ObjectMapMismatch,
+    UnsupportedVersion,
 };
 use std::collections::HashMap;
 
@@ -12,6 +13,8 @@ pub enum DatabaseParseError {
     InvalidObjectMap(ObjectMappingError),
     InvalidObject(ObjectDecodeError),
     InvalidObjectOffset(ObjectMapping),
+    /// The table of contents information in the object map does not match the object.
+    ObjectMapMismatch(ObjectMapping),
     UnsupportedVersion(u32),
     HeaderParsingError(&'static str),
     FileOpenFailed(std::io::Error),
@@ -42,8 +45,9 @@ impl RawDatabaseFile {
         let header_size = header.header_len as usize;
         let remainig_bytes = &value[header_size..];
 
-        let object_map = ObjectMap::try_from(remainig_bytes, header.number_of_objects)
-            .map_err(InvalidObjectMap)?;
+        let object_map =
+            ObjectMap::try_from_version(remainig_bytes, header.number_of_objects, header.version)
+                .map_err(InvalidObjectMap)?;
         Ok((header, object_map))
     }
 
@@ -52,7 +56,7 @@ impl RawDatabaseFile {
         object_map: &ObjectMap,
     ) -> Result<HashMap<u64, RawObject>, DatabaseParseError> {
         let mut objects = HashMap::new();
-        for mapping in &object_map.mappings {
+        for mapping in object_map.mappings() {
             let start_pos = mapping.offset;
             if start_pos >= data.len() as u64 {
                 return Err(InvalidObjectOffset(mapping.clone()));
@@ -60,6 +64,17 @@ impl RawDatabaseFile {
 
             let object_slice = &data[start_pos as usize..];
             let object = RawObject::try_from(object_slice).map_err(InvalidObject)?;
+            if let Some(info) = &mapping.info {
+                if info.format != object.format
+                    || info.compression != object.compression
+                    || info.entry_type != object.entry_type
+                    || info.entry_size != object.entry_size
+                    || info.length != object.length
+                    || info.entry_count != object.data.len() as u64
+                {
+                    return Err(ObjectMapMismatch(mapping.clone()));
+                }
+            }
             objects.insert(mapping.id, object);
         }
         Ok(objects)
@@ -73,7 +88,8 @@ impl TryFrom<&[u8]> for RawDatabaseFile {
         let version = Header::partial_version(value).map_err(InvalidHeader)?;
 
         match version {
-            0x0001 => Self::parse_v1(value),
+            // Version 2 and 3 only extend the object map of the v1 layout
+            0x0001..=0x0003 => Self::parse_v1(value),
             v => Err(UnsupportedVersion(v)),
         }
     }
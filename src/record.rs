@@ -0,0 +1,209 @@
+//! Fixed-layout records, the building blocks of simple object formats.
+//!
+//! A [`DatabaseRecord`] is a single entry with a fixed size, e.g. a digest and a SHA256 hash.
+//! [`RecordObject`] stores a list of records and implements [`ObjectImpl`] and
+//! [`ObjectFormat`] for them, checking the format, entry type and entry size when decoding.
+//!
+//! With the `derive` feature, `#[derive(DatabaseRecord)]` generates the implementation for
+//! structs of byte arrays and integers. The fields are stored in declaration order, integers
+//! in big-endian byte order unless marked with `#[record(little_endian)]`.
+//!
+//! # Example: Define a record format
+//! ```rust ignore
+//! use simbiota_database::record::{DatabaseRecord, RecordObject};
+//! use simbiota_database::ObjectImpl;
+//!
+//! #[derive(DatabaseRecord)]
+//! #[record(format = 0x0003, entry_type = 0)]
+//! struct DistanceRecord {
+//!     tlsh: [u8; 36],
+//!     sha256: [u8; 32],
+//!     distance: u8,
+//! }
+//!
+//! let mut object = RecordObject::new();
+//! object.add_record(DistanceRecord { tlsh: [0; 36], sha256: [0; 32], distance: 40 });
+//! let generic_object = object.to_object();
+//! ```
+
+use crate::database::{
+    check_entry_size, FormatError, Object, ObjectCompressionType, ObjectFormat, ObjectImpl,
+};
+
+#[cfg(feature = "derive")]
+pub use simbiota_database_derive::DatabaseRecord;
+
+/// A fixed size object entry.
+pub trait DatabaseRecord: Sized {
+    /// Name of the object format, used as [`ObjectImpl::NAME`] of the [`RecordObject`].
+    const NAME: &'static str;
+    const FORMAT: u16;
+    const ENTRY_TYPE: u16;
+    /// Size of the serialized record.
+    const ENTRY_SIZE: u16;
+
+    /// Serialize the record, the result must be `ENTRY_SIZE` bytes long.
+    fn to_entry(&self) -> Vec<u8>;
+    /// Parse a record. Returns none if the entry is invalid.
+    fn from_entry(entry: &[u8]) -> Option<Self>;
+}
+
+/// A field type of a derived [`DatabaseRecord`].
+pub trait RecordField: Sized {
+    /// Size of the serialized field.
+    const SIZE: usize;
+
+    fn write(&self, entry: &mut Vec<u8>, big_endian: bool);
+    /// Parse the field from exactly `SIZE` bytes.
+    fn read(bytes: &[u8], big_endian: bool) -> Option<Self>;
+}
+
+impl<const N: usize> RecordField for [u8; N] {
+    const SIZE: usize = N;
+
+    fn write(&self, entry: &mut Vec<u8>, _: bool) {
+        entry.extend_from_slice(self);
+    }
+
+    fn read(bytes: &[u8], _: bool) -> Option<Self> {
+        bytes.try_into().ok()
+    }
+}
+
+macro_rules! integer_record_field {
+    ($($int:ty),*) => {
+        $(
+            impl RecordField for $int {
+                const SIZE: usize = std::mem::size_of::<$int>();
+
+                fn write(&self, entry: &mut Vec<u8>, big_endian: bool) {
+                    if big_endian {
+                        entry.extend_from_slice(&self.to_be_bytes());
+                    } else {
+                        entry.extend_from_slice(&self.to_le_bytes());
+                    }
+                }
+
+                fn read(bytes: &[u8], big_endian: bool) -> Option<Self> {
+                    let bytes = bytes.try_into().ok()?;
+                    Some(if big_endian {
+                        <$int>::from_be_bytes(bytes)
+                    } else {
+                        <$int>::from_le_bytes(bytes)
+                    })
+                }
+            }
+        )*
+    };
+}
+
+integer_record_field!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// Generic object storing a list of [`DatabaseRecord`]s.
+pub struct RecordObject<T: DatabaseRecord> {
+    records: Vec<T>,
+}
+
+impl<T: DatabaseRecord> RecordObject<T> {
+    pub fn new() -> Self {
+        Self { records: vec![] }
+    }
+
+    pub fn from_records(records: Vec<T>) -> Self {
+        Self { records }
+    }
+
+    pub fn get_records(&self) -> &Vec<T> {
+        &self.records
+    }
+
+    pub fn add_record(&mut self, record: T) {
+        self.records.push(record);
+    }
+
+    pub fn into_records(self) -> Vec<T> {
+        self.records
+    }
+}
+
+impl<T: DatabaseRecord> Default for RecordObject<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: DatabaseRecord> ObjectImpl for RecordObject<T> {
+    const NAME: &'static str = T::NAME;
+
+    fn to_object(self) -> Object {
+        Object {
+            format: T::FORMAT,
+            compression_type: ObjectCompressionType::NoCompression,
+            entry_type: T::ENTRY_TYPE,
+            entry_size: T::ENTRY_SIZE,
+            data: self
+                .records
+                .iter()
+                .map(|r| {
+                    let entry = r.to_entry();
+                    assert_eq!(entry.len(), T::ENTRY_SIZE as usize, "invalid record size");
+                    entry
+                })
+                .collect(),
+        }
+    }
+
+    fn from_object(obj: Object) -> Option<Self> {
+        Self::try_from_object(&obj).ok()
+    }
+}
+
+impl<T: DatabaseRecord> ObjectFormat for RecordObject<T> {
+    const FORMAT: u16 = T::FORMAT;
+    const ENTRY_TYPES: &'static [u16] = &[T::ENTRY_TYPE];
+
+    /// Every entry must be a valid record.
+    fn try_from_object(obj: &Object) -> Result<Self, FormatError> {
+        Self::check_object(obj)?;
+        check_entry_size(obj, T::ENTRY_SIZE)?;
+
+        let records = obj
+            .data
+            .iter()
+            .enumerate()
+            .map(|(index, e)| T::from_entry(e).ok_or(FormatError::MalformedEntry(index)))
+            .collect::<Result<Vec<T>, FormatError>>()?;
+        Ok(Self { records })
+    }
+}
+
+impl<T: DatabaseRecord> TryFrom<&Object> for RecordObject<T> {
+    type Error = FormatError;
+
+    fn try_from(obj: &Object) -> Result<Self, Self::Error> {
+        Self::try_from_object(obj)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::record::RecordField;
+
+    #[test]
+    pub fn test_record_fields() {
+        let mut entry = Vec::new();
+        0x0102u16.write(&mut entry, true);
+        0x0102u16.write(&mut entry, false);
+        [7u8; 3].write(&mut entry, true);
+        (-2i32).write(&mut entry, true);
+        assert_eq!(entry, vec![1, 2, 2, 1, 7, 7, 7, 0xff, 0xff, 0xff, 0xfe]);
+
+        assert_eq!(u16::read(&entry[0..2], true), Some(0x0102));
+        assert_eq!(u16::read(&entry[2..4], false), Some(0x0102));
+        assert_eq!(<[u8; 3]>::read(&entry[4..7], true), Some([7; 3]));
+        assert_eq!(i32::read(&entry[7..11], true), Some(-2));
+        assert_eq!(u32::read(&entry[0..3], true), None);
+        assert_eq!(<[u8; 36]>::SIZE, 36);
+        assert_eq!(u64::SIZE, 8);
+    }
+}
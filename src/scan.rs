@@ -0,0 +1,246 @@
+//! Allowlist-aware sample lookup.
+//!
+//! [`Database::scan`](crate::Database::scan) checks a sample against every detection object of
+//! a database, then checks the allowlist objects. If an allowlist entry covers the sample, the
+//! hits are reported as suppressed, together with the allowlist entry responsible.
+//!
+//! # Example: Scan a sample
+//! ```rust no_run
+//! use simbiota_database::hash::Sha256;
+//! use simbiota_database::tlsh::Tlsh;
+//! use simbiota_database::Database;
+//!
+//! let file_data = std::fs::read("database_file.sdb").unwrap();
+//! let database = Database::from_bytes(file_data.as_slice()).expect("failed to load database");
+//!
+//! let sample = std::fs::read("/bin/busybox").unwrap();
+//! let sha256_hash = Sha256::from([0u8; 32]); // SHA256 hash of the sample
+//! let result = database.scan(&sha256_hash, Tlsh::hash(&sample).as_ref(), 40);
+//! if let Some(suppression) = result.suppressed_by {
+//!     let entry = suppression.allowlist_match.index;
+//!     println!("allowed by entry {} of object {}", entry, suppression.object_id);
+//! } else if result.is_detected() {
+//!     println!("detected, {} hits", result.hits.len());
+//! }
+//! ```
+
+use crate::database::{Object, ObjectFormat};
+use crate::formats::allowlist::{AllowlistMatch, AllowlistObject};
+use crate::formats::colored_tlsh_with_distance::ColoredTLSHWithDistanceObject;
+use crate::formats::sha256_set::SHA256SetObject;
+use crate::hash::Sha256;
+use crate::search::indexed_entries;
+use crate::tlsh::Tlsh;
+
+/// A detection object entry matching the sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScanHit {
+    /// ID of the detection object.
+    pub object_id: u64,
+    /// Index of the matching entry in the object.
+    pub index: usize,
+    /// SHA256 hash of the matching sample, if the object format stores it.
+    pub sha256: Option<Sha256>,
+    /// TLSH distance of the sample from the entry, none for exact SHA256 matches.
+    pub distance: Option<u32>,
+}
+
+/// The allowlist entry that suppressed the hits of a scan.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Suppression {
+    /// ID of the allowlist object.
+    pub object_id: u64,
+    pub allowlist_match: AllowlistMatch,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanResult {
+    /// Every hit, ordered by object ID and entry index.
+    pub hits: Vec<ScanHit>,
+    /// The allowlist entry covering the sample, if any.
+    pub suppressed_by: Option<Suppression>,
+}
+
+impl ScanResult {
+    /// Whether the sample has hits that are not suppressed by an allowlist.
+    pub fn is_detected(&self) -> bool {
+        !self.hits.is_empty() && self.suppressed_by.is_none()
+    }
+}
+
+/// Scan a sample against the objects of a database.
+///
+/// Detection objects are SimpleTLSH (0x0001) and ColoredTLSH (0x0002) objects, matched with
+/// `tlsh_threshold`, ColoredTLSHWithDistance (0x0003) objects, matched with the threshold of
+/// each entry, and SHA256Set (0x0005) objects. Allowlist (0x000C) objects suppress the hits, an
+/// exact SHA256 entry takes precedence over the closest TLSH entry. Invalid objects are skipped.
+pub(crate) fn scan<'a>(
+    objects: impl Iterator<Item = (u64, &'a Object)>,
+    sha256_hash: &Sha256,
+    tlsh: Option<&Tlsh>,
+    tlsh_threshold: u32,
+) -> ScanResult {
+    let mut objects: Vec<(u64, &Object)> = objects.collect();
+    objects.sort_by_key(|(id, _)| *id);
+
+    let mut hits = Vec::new();
+    let mut suppressions = Vec::new();
+    for (object_id, obj) in objects {
+        match obj.format {
+            0x0001 | 0x0002 => {
+                let (Some(tlsh), Some(entries)) = (tlsh, indexed_entries(obj)) else {
+                    continue;
+                };
+                for (index, entry) in entries.iter().enumerate() {
+                    let distance = tlsh.distance(&entry.tlsh);
+                    if distance <= tlsh_threshold {
+                        hits.push(ScanHit {
+                            object_id,
+                            index,
+                            sha256: entry.sha256,
+                            distance: Some(distance),
+                        });
+                    }
+                }
+            }
+            0x0003 => {
+                let (Some(tlsh), Some(object)) = (
+                    tlsh,
+                    ColoredTLSHWithDistanceObject::try_from_object(obj).ok(),
+                ) else {
+                    continue;
+                };
+                for (index, entry) in object.get_entries().iter().enumerate() {
+                    let distance = tlsh.distance(&entry.tlsh.to_tlsh());
+                    if distance <= entry.distance as u32 {
+                        hits.push(ScanHit {
+                            object_id,
+                            index,
+                            sha256: Some(entry.sha256_hash),
+                            distance: Some(distance),
+                        });
+                    }
+                }
+            }
+            0x0005 => {
+                let Some(set) = SHA256SetObject::try_from_object(obj).ok() else {
+                    continue;
+                };
+                if let Ok(index) = set.get_hashes().binary_search(sha256_hash) {
+                    hits.push(ScanHit {
+                        object_id,
+                        index,
+                        sha256: Some(*sha256_hash),
+                        distance: None,
+                    });
+                }
+            }
+            0x000C => {
+                let Some(allowlist) = AllowlistObject::try_from_object(obj).ok() else {
+                    continue;
+                };
+                if let Some(allowlist_match) = allowlist.find(sha256_hash, tlsh) {
+                    suppressions.push(Suppression {
+                        object_id,
+                        allowlist_match,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Objects are visited in ID order, so the first of the equally close entries wins
+    let suppressed_by = suppressions
+        .into_iter()
+        .min_by_key(|s| s.allowlist_match.distance);
+    ScanResult {
+        hits,
+        suppressed_by,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::database::ObjectImpl;
+    use crate::formats::allowlist::AllowlistObject;
+    use crate::formats::colored_tlsh::ColoredTLSHObject;
+    use crate::formats::colored_tlsh_with_distance::ColoredTLSHWithDistanceObject;
+    use crate::formats::sha256_set::SHA256SetObject;
+    use crate::hash::Sha256;
+    use crate::scan::scan;
+    use crate::tlsh::{test_digest, Tlsh, COLORED_TLSH_LENGTH, TLSH_LENGTH};
+
+    #[test]
+    pub fn test_scan() {
+        let mut colored = ColoredTLSHObject::empty();
+        colored.add_entry(test_digest::<COLORED_TLSH_LENGTH>(2), [1; 32]);
+        colored.add_entry(test_digest::<COLORED_TLSH_LENGTH>(3), [2; 32]);
+        let mut with_distance = ColoredTLSHWithDistanceObject::empty();
+        with_distance.add_entry(test_digest::<COLORED_TLSH_LENGTH>(3), [3; 32], 6);
+        with_distance.add_entry(test_digest::<COLORED_TLSH_LENGTH>(1), [4; 32], 0);
+        let set = SHA256SetObject::from_hashes([[5; 32], [9; 32]]);
+        let objects = [
+            (2, with_distance.to_object()),
+            (1, colored.to_object()),
+            (3, set.to_object()),
+        ];
+        let objects = || objects.iter().map(|(id, o)| (*id, o));
+        let query = Tlsh::from_bytes(&[0u8; 36]).unwrap();
+
+        let result = scan(objects(), &Sha256::from([9; 32]), Some(&query), 2);
+        let hits: Vec<(u64, usize, Option<u32>)> = result
+            .hits
+            .iter()
+            .map(|h| (h.object_id, h.index, h.distance))
+            .collect();
+        assert_eq!(hits, vec![(1, 0, Some(2)), (2, 0, Some(6)), (3, 1, None)]);
+        assert!(result.is_detected());
+
+        let result = scan(objects(), &Sha256::default(), None, 2);
+        assert!(result.hits.is_empty());
+        assert!(!result.is_detected());
+
+        let mut tlsh_allowlist = AllowlistObject::empty();
+        tlsh_allowlist.add_tlsh(test_digest::<TLSH_LENGTH>(1), 5);
+        let mut sha_allowlist = AllowlistObject::empty();
+        sha_allowlist.add_tlsh([0u8; 35], 0);
+        sha_allowlist.add_sha256([9; 32]);
+        let allowlists = [
+            (10, tlsh_allowlist.to_object()),
+            (11, sha_allowlist.to_object()),
+        ];
+        let all = || objects().chain(allowlists.iter().map(|(id, o)| (*id, o)));
+
+        // The exact SHA256 entry wins over the closer TLSH entry
+        let result = scan(all(), &Sha256::from([9; 32]), Some(&query), 2);
+        assert_eq!(result.hits.len(), 3);
+        assert!(!result.is_detected());
+        let suppression = result.suppressed_by.unwrap();
+        assert_eq!(suppression.object_id, 11);
+        assert_eq!(suppression.allowlist_match.index, 1);
+
+        let suppression = scan(all(), &Sha256::default(), Some(&query), 2)
+            .suppressed_by
+            .unwrap();
+        assert_eq!(suppression.object_id, 11);
+        assert_eq!(suppression.allowlist_match.distance, Some(0));
+    }
+
+    #[test]
+    pub fn test_scan_invalid_set() {
+        let mut unsorted = SHA256SetObject::empty().to_object();
+        unsorted.data = vec![vec![9; 32], vec![5; 32]];
+        let mut short = SHA256SetObject::empty().to_object();
+        short.data = vec![vec![9; 31]];
+        let objects = [(1, unsorted), (2, short)];
+
+        let result = scan(
+            objects.iter().map(|(id, o)| (*id, o)),
+            &Sha256::from([9; 32]),
+            None,
+            0,
+        );
+        assert!(result.hits.is_empty());
+    }
+}
@@ -0,0 +1,508 @@
+//! Nearest-neighbour search over TLSH objects.
+//!
+//! [`TlshIndex`] is a BK-tree built over the digests of a SimpleTLSH, ColoredTLSH,
+//! ColoredTLSHWithDistance or Telfhash object. The TLSH distance itself does not satisfy the triangle
+//! inequality, so the tree is keyed by the L1 distance of the digest bodies, which is a metric and
+//! a lower bound of the TLSH distance. Candidates are always verified with the full TLSH distance,
+//! the results are exact.
+//!
+//! # Example: Find the samples close to a digest
+//! ```rust no_run
+//! use simbiota_database::search::TlshIndex;
+//! use simbiota_database::tlsh::Tlsh;
+//! use simbiota_database::Database;
+//!
+//! let file_data = std::fs::read("database_file.sdb").unwrap();
+//! let database = Database::from_bytes(file_data.as_slice()).expect("failed to load database");
+//! let object = database.get_object(0x0002).expect("object not found");
+//! let index = TlshIndex::from_object(object).expect("not a TLSH object");
+//!
+//! let digest = Tlsh::from_hex("B911A8DACB5B5A06568B6ED299B18014C811DD897E95B720B871B1F5EF7300538187DC").unwrap();
+//! for result in index.within(&digest, 50) {
+//!     println!("entry {} at distance {}", result.index, result.distance);
+//! }
+//! ```
+
+use crate::database::{Object, ObjectFormat};
+use crate::formats::colored_tlsh::ColoredTLSHObject;
+use crate::formats::colored_tlsh_with_distance::ColoredTLSHWithDistanceObject;
+use crate::formats::simple_tlsh::SimpleTLSHObject;
+use crate::formats::telfhash::TelfhashObject;
+use crate::formats::tlsh_index::TLSHIndexObject;
+use crate::hash::{Sha256, TlshDigest};
+use crate::search::IndexLoadError::{InvalidIndex, InvalidIndexedObject, Stale};
+use crate::source::{DatabaseSource, SourceError};
+use crate::tlsh::Tlsh;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+/// Error representing failures that can occur while loading a persisted index.
+#[derive(Debug)]
+pub enum IndexLoadError {
+    /// Reading an object from the database failed.
+    SourceError(SourceError),
+    /// The object with the given ID is not a valid TLSHIndex object.
+    InvalidIndex(u64),
+    /// The indexed object with the given ID is not a supported TLSH object.
+    InvalidIndexedObject(u64),
+    /// The indexed object with the given ID changed since the index was built.
+    Stale(u64),
+}
+
+/// A digest stored in the index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexedEntry {
+    pub tlsh: Tlsh,
+    /// SHA-256 of the sample, if the object format stores it.
+    pub sha256: Option<Sha256>,
+}
+
+/// A single search hit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchResult {
+    /// Index of the entry in the indexed object.
+    pub index: usize,
+    /// SHA-256 of the sample, if the object format stores it.
+    pub sha256: Option<Sha256>,
+    /// TLSH distance from the query digest, including the length component.
+    pub distance: u32,
+}
+
+/// Node of the flattened BK-tree. The children of every node are stored next to each other,
+/// ordered by their distance from the parent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct IndexNode {
+    /// Index of the entry in the indexed object.
+    pub(crate) entry: u32,
+    /// Body distance from the parent node, zero for the root.
+    pub(crate) distance: u16,
+    pub(crate) first_child: u32,
+    pub(crate) child_count: u32,
+}
+
+/// In-memory BK-tree over the TLSH digests of an object.
+pub struct TlshIndex {
+    entries: Vec<IndexedEntry>,
+    nodes: Vec<IndexNode>,
+}
+
+impl TlshIndex {
+    /// Build an index over the digests of a TLSH object.
+    ///
+    /// Supports formats 0x0001 (SimpleTLSH), 0x0002 (ColoredTLSH), 0x0003
+    /// (ColoredTLSHWithDistance) and 0x0008 (Telfhash). Returns none for other formats or if the
+    /// object is not valid in its format.
+    pub fn from_object(obj: &Object) -> Option<Self> {
+        let entries = indexed_entries(obj)?;
+        let nodes = build_tree(&entries);
+        Some(Self { entries, nodes })
+    }
+
+    /// Use a prebuilt index without rebuilding the tree.
+    ///
+    /// `indexed` must be the object referenced by the index, it is checked for staleness.
+    pub fn from_index_object(
+        index: TLSHIndexObject,
+        indexed: &Object,
+    ) -> Result<Self, IndexLoadError> {
+        let indexed_id = index.indexed_object();
+        if index.is_stale(indexed) {
+            return Err(Stale(indexed_id));
+        }
+        let entries = indexed_entries(indexed).ok_or(InvalidIndexedObject(indexed_id))?;
+        Ok(Self {
+            entries,
+            nodes: index.nodes,
+        })
+    }
+
+    /// Load a prebuilt index and the object it references from any database backend.
+    pub fn load<S: DatabaseSource + ?Sized>(
+        source: &S,
+        index_id: u64,
+    ) -> Result<Self, IndexLoadError> {
+        let index = source
+            .get_object(index_id)
+            .map_err(IndexLoadError::SourceError)?;
+        let index = TLSHIndexObject::try_from_object(&index).map_err(|_| InvalidIndex(index_id))?;
+        let indexed = source
+            .get_object(index.indexed_object())
+            .map_err(IndexLoadError::SourceError)?;
+        Self::from_index_object(index, &indexed)
+    }
+
+    /// Number of indexed digests.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The indexed digests, in the order of the object entries.
+    pub fn entries(&self) -> &[IndexedEntry] {
+        &self.entries
+    }
+
+    /// Every entry within `max_distance` of the digest, ordered by distance.
+    pub fn within(&self, digest: &Tlsh, max_distance: u32) -> Vec<SearchResult> {
+        let mut results = Vec::new();
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0usize);
+        }
+
+        while let Some(node_index) = stack.pop() {
+            let node = self.nodes[node_index];
+            let body_distance = body_l1(digest, &self.entries[node.entry as usize].tlsh);
+            if body_distance <= max_distance {
+                let result = self.result(digest, node.entry);
+                if result.distance <= max_distance {
+                    results.push(result);
+                }
+            }
+            stack.extend(
+                self.children(&node)
+                    .filter(|(_, child)| {
+                        (child.distance as u32).abs_diff(body_distance) <= max_distance
+                    })
+                    .map(|(i, _)| i),
+            );
+        }
+
+        sort_results(&mut results);
+        results
+    }
+
+    /// The `k` entries closest to the digest, ordered by distance.
+    pub fn top_k(&self, digest: &Tlsh, k: usize) -> Vec<SearchResult> {
+        if k == 0 || self.nodes.is_empty() {
+            return Vec::new();
+        }
+
+        // Best k results so far, the worst one on top
+        let mut best: BinaryHeap<(u32, usize)> = BinaryHeap::with_capacity(k + 1);
+        // Nodes to visit, with the lower bound of the distance of their whole subtree
+        let mut queue = BinaryHeap::new();
+        queue.push(Reverse((0u32, 0usize)));
+
+        while let Some(Reverse((lower_bound, node_index))) = queue.pop() {
+            if best.len() == k && lower_bound > best.peek().unwrap().0 {
+                break;
+            }
+            let node = self.nodes[node_index];
+            let entry = &self.entries[node.entry as usize].tlsh;
+            best.push((digest.distance(entry), node.entry as usize));
+            if best.len() > k {
+                best.pop();
+            }
+
+            let body_distance = body_l1(digest, entry);
+            for (child_index, child) in self.children(&node) {
+                let child_bound = lower_bound.max((child.distance as u32).abs_diff(body_distance));
+                if best.len() < k || child_bound <= best.peek().unwrap().0 {
+                    queue.push(Reverse((child_bound, child_index)));
+                }
+            }
+        }
+
+        let mut results: Vec<SearchResult> = best
+            .into_iter()
+            .map(|(_, entry)| self.result(digest, entry as u32))
+            .collect();
+        sort_results(&mut results);
+        results
+    }
+
+    fn children(&self, node: &IndexNode) -> impl Iterator<Item = (usize, &IndexNode)> {
+        let first = node.first_child as usize;
+        let range = first..first + node.child_count as usize;
+        self.nodes[range.clone()]
+            .iter()
+            .enumerate()
+            .map(move |(i, n)| (range.start + i, n))
+    }
+
+    fn result(&self, digest: &Tlsh, entry: u32) -> SearchResult {
+        let indexed = &self.entries[entry as usize];
+        SearchResult {
+            index: entry as usize,
+            sha256: indexed.sha256,
+            distance: digest.distance(&indexed.tlsh),
+        }
+    }
+}
+
+fn sort_results(results: &mut [SearchResult]) {
+    results.sort_by_key(|r| (r.distance, r.index));
+}
+
+/// Parse the digests of a supported TLSH object, through the object type of its format.
+pub(crate) fn indexed_entries(obj: &Object) -> Option<Vec<IndexedEntry>> {
+    let entry = |tlsh: &TlshDigest, sha256: Option<Sha256>| IndexedEntry {
+        tlsh: tlsh.to_tlsh(),
+        sha256,
+    };
+    let entries = match obj.format {
+        0x0001 => SimpleTLSHObject::try_from_object(obj)
+            .ok()?
+            .get_digests()
+            .iter()
+            .map(|d| entry(d, None))
+            .collect(),
+        0x0002 => ColoredTLSHObject::try_from_object(obj)
+            .ok()?
+            .get_entries()
+            .iter()
+            .map(|e| entry(&e.tlsh, Some(e.sha256_hash)))
+            .collect(),
+        0x0003 => ColoredTLSHWithDistanceObject::try_from_object(obj)
+            .ok()?
+            .get_entries()
+            .iter()
+            .map(|e| entry(&e.tlsh, Some(e.sha256_hash)))
+            .collect(),
+        0x0008 => TelfhashObject::try_from_object(obj)
+            .ok()?
+            .get_entries()
+            .iter()
+            .map(|e| entry(&e.telfhash, Some(e.sha256_hash)))
+            .collect(),
+        _ => return None,
+    };
+    Some(entries)
+}
+
+/// L1 distance of the digest bodies, counting each 2-bit bucket. This is a metric and never
+/// larger than the TLSH distance.
+fn body_l1(a: &Tlsh, b: &Tlsh) -> u32 {
+    a.body()
+        .iter()
+        .zip(b.body().iter())
+        .map(|(x, y)| {
+            (0..4)
+                .map(|i| (((x >> (i * 2)) & 0b11) as u32).abs_diff(((y >> (i * 2)) & 0b11) as u32))
+                .sum::<u32>()
+        })
+        .sum()
+}
+
+/// Build the BK-tree and flatten it breadth-first, so the children of each node are adjacent.
+pub(crate) fn build_tree(entries: &[IndexedEntry]) -> Vec<IndexNode> {
+    struct BuildNode {
+        entry: u32,
+        children: Vec<(u16, usize)>,
+    }
+
+    let mut tree: Vec<BuildNode> = Vec::with_capacity(entries.len());
+    for (entry_index, entry) in entries.iter().enumerate() {
+        let new_node = BuildNode {
+            entry: entry_index as u32,
+            children: Vec::new(),
+        };
+        if tree.is_empty() {
+            tree.push(new_node);
+            continue;
+        }
+
+        let mut current = 0;
+        loop {
+            let distance = body_l1(&entries[tree[current].entry as usize].tlsh, &entry.tlsh) as u16;
+            match tree[current].children.iter().find(|(d, _)| *d == distance) {
+                Some((_, child)) => current = *child,
+                None => {
+                    let new_index = tree.len();
+                    tree[current].children.push((distance, new_index));
+                    tree.push(new_node);
+                    break;
+                }
+            }
+        }
+    }
+
+    let mut nodes = Vec::with_capacity(tree.len());
+    if tree.is_empty() {
+        return nodes;
+    }
+    let mut queue = VecDeque::from([(0usize, 0u16)]);
+    while let Some((tree_index, distance)) = queue.pop_front() {
+        let node = &mut tree[tree_index];
+        node.children.sort_by_key(|(d, _)| *d);
+        // Children are appended after every node already queued
+        let first_child = nodes.len() + queue.len() + 1;
+        nodes.push(IndexNode {
+            entry: node.entry,
+            distance,
+            first_child: first_child as u32,
+            child_count: node.children.len() as u32,
+        });
+        queue.extend(node.children.iter().map(|(d, i)| (*i, *d)));
+    }
+    nodes
+}
+
+#[cfg(test)]
+mod test {
+    use crate::database::ObjectImpl;
+    use crate::formats::colored_tlsh::{ColoredTLSHObject, FamilyLabel};
+    use crate::formats::simple_tlsh::{SimpleTLSHEntryType, SimpleTLSHObject};
+    use crate::formats::telfhash::TelfhashObject;
+    use crate::hash::{Sha256, TlshDigest};
+    use crate::search::{SearchResult, TlshIndex};
+    use crate::tlsh::Tlsh;
+
+    /// Deterministic pseudo-random digests, with small variations of a few base digests
+    fn digests(count: usize) -> Vec<Vec<u8>> {
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let bases: Vec<Vec<u8>> = (0..4)
+            .map(|_| (0..36).map(|_| next() as u8).collect())
+            .collect();
+        (0..count)
+            .map(|i| {
+                let mut digest = bases[i % bases.len()].clone();
+                for _ in 0..(next() % 12) {
+                    let pos = (next() % 35) as usize;
+                    digest[pos] ^= 1 << (next() % 8);
+                }
+                digest
+            })
+            .collect()
+    }
+
+    fn colored_index(digests: &[Vec<u8>]) -> TlshIndex {
+        let mut object = ColoredTLSHObject::empty();
+        for (i, digest) in digests.iter().enumerate() {
+            object.add_entry(TlshDigest::from_bytes(digest).unwrap(), [i as u8; 32]);
+        }
+        TlshIndex::from_object(&object.to_object()).unwrap()
+    }
+
+    fn linear_scan(digests: &[Vec<u8>], query: &Tlsh) -> Vec<SearchResult> {
+        let mut results: Vec<SearchResult> = digests
+            .iter()
+            .enumerate()
+            .map(|(i, d)| SearchResult {
+                index: i,
+                sha256: Some(Sha256::from([i as u8; 32])),
+                distance: query.distance(&Tlsh::from_bytes(d).unwrap()),
+            })
+            .collect();
+        results.sort_by_key(|r| (r.distance, r.index));
+        results
+    }
+
+    #[test]
+    pub fn test_within() {
+        let digests = digests(200);
+        let index = colored_index(&digests);
+        assert_eq!(index.len(), 200);
+
+        for query in digests.iter().step_by(17) {
+            let query = Tlsh::from_bytes(query).unwrap();
+            for max_distance in [0, 10, 50, 150, 1000] {
+                let expected: Vec<SearchResult> = linear_scan(&digests, &query)
+                    .into_iter()
+                    .filter(|r| r.distance <= max_distance)
+                    .collect();
+                assert_eq!(index.within(&query, max_distance), expected);
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_top_k() {
+        let digests = digests(200);
+        let index = colored_index(&digests);
+
+        for query in digests.iter().step_by(23) {
+            let query = Tlsh::from_bytes(query).unwrap();
+            let expected = linear_scan(&digests, &query);
+            for k in [0, 1, 5, 50, 300] {
+                let results = index.top_k(&query, k);
+                assert_eq!(results.len(), k.min(digests.len()));
+                // Ties at the boundary can be resolved either way, compare the distances
+                let distances: Vec<u32> = results.iter().map(|r| r.distance).collect();
+                let expected: Vec<u32> = expected.iter().take(k).map(|r| r.distance).collect();
+                assert_eq!(distances, expected);
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_simple_tlsh_index() {
+        let hash = "B911A8DACB5B5A06568B6ED299B18014C811DD897E95B720B871B1F5EF7300538187DC";
+        let mut object = SimpleTLSHObject::new(SimpleTLSHEntryType::HEX);
+        object.add_hash(hash.to_string());
+        let index = TlshIndex::from_object(&object.to_object()).unwrap();
+
+        let results = index.top_k(&Tlsh::from_hex(hash).unwrap(), 1);
+        assert_eq!(
+            results,
+            vec![SearchResult {
+                index: 0,
+                sha256: None,
+                distance: 0
+            }]
+        );
+        assert!(
+            TlshIndex::from_object(&ColoredTLSHObject::empty().to_object())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    pub fn test_labelled_and_invalid_objects() {
+        let digests = digests(2);
+        let mut object = ColoredTLSHObject::empty();
+        object.add_entry(TlshDigest::from_bytes(&digests[0]).unwrap(), [1; 32]);
+        let label = FamilyLabel {
+            string_table_id: 9,
+            index: 2,
+        };
+        object.add_labelled_entry(TlshDigest::from_bytes(&digests[1]).unwrap(), [2; 32], label);
+        let object = object.to_object();
+        let index = TlshIndex::from_object(&object).unwrap();
+        let sha256: Vec<Option<Sha256>> = index.entries().iter().map(|e| e.sha256).collect();
+        assert_eq!(sha256, vec![Some([1; 32].into()), Some([2; 32].into())]);
+
+        // Objects rejected by their format are not indexed
+        let mut truncated = object.clone();
+        truncated.data[1].pop();
+        assert!(TlshIndex::from_object(&truncated).is_none());
+        let mut unknown_entry_type = object;
+        unknown_entry_type.entry_type = 7;
+        assert!(TlshIndex::from_object(&unknown_entry_type).is_none());
+    }
+
+    #[test]
+    pub fn test_telfhash_index() {
+        let digests = digests(20);
+        let mut object = TelfhashObject::empty();
+        for (i, digest) in digests.iter().enumerate() {
+            object.add_entry(
+                TlshDigest::from_bytes(&digest[0..35]).unwrap(),
+                [i as u8; 32],
+            );
+        }
+        let index = TlshIndex::from_object(&object.to_object()).unwrap();
+
+        let query = Tlsh::from_bytes(&digests[5]).unwrap();
+        assert_eq!(
+            index.top_k(&query, 1)[0].sha256,
+            Some(Sha256::from([5; 32]))
+        );
+        let expected: Vec<SearchResult> = linear_scan(&digests, &query)
+            .into_iter()
+            .filter(|r| r.distance <= 100)
+            .collect();
+        assert_eq!(index.within(&query, 100), expected);
+    }
+}
@@ -0,0 +1,120 @@
+//! Thread-safe database handle that can be replaced while it is being queried.
+//!
+//! A [`SharedDatabase`] holds an immutable snapshot of a database. Any number of threads can
+//! take a [`snapshot`](SharedDatabase::snapshot) and query it, while an updater thread installs a
+//! freshly loaded database with [`replace`](SharedDatabase::replace). Neither operation takes a
+//! lock, and queries in flight keep using the snapshot they started with.
+//!
+//! # Example: Hot swapping the database
+//! ```rust no_run
+//! use std::path::Path;
+//! use std::sync::Arc;
+//! use simbiota_database::shared::SharedDatabase;
+//! use simbiota_database::{Database, DatabaseSource, LazyLoadedDatabase};
+//!
+//! let file_data = std::fs::read(Path::new("database_file.sdb")).unwrap();
+//! let database = Database::from_bytes(file_data.as_slice()).expect("failed to load database");
+//! let shared = Arc::new(SharedDatabase::from_source(database));
+//!
+//! let scanner_db = shared.clone();
+//! let scanner = std::thread::spawn(move || {
+//!     let snapshot = scanner_db.snapshot();
+//!     snapshot.has_object(0x0001).unwrap()
+//! });
+//!
+//! let updated = LazyLoadedDatabase::new(Path::new("database_file.sdb")).unwrap();
+//! shared.replace_source(updated);
+//! scanner.join().unwrap();
+//! ```
+
+use crate::source::DatabaseSource;
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+
+/// Type-erased database backend that can be shared between threads.
+pub type DynDatabaseSource = Box<dyn DatabaseSource + Send + Sync>;
+
+/// Shared handle for a database that can be replaced without blocking the readers.
+///
+/// By default the handle is type-erased, so an in-memory [`Database`](crate::Database) can be
+/// replaced by a [`LazyLoadedDatabase`](crate::LazyLoadedDatabase) and vice versa. Use a concrete
+/// type parameter to avoid the dynamic dispatch.
+pub struct SharedDatabase<S = DynDatabaseSource> {
+    current: ArcSwap<S>,
+}
+
+impl<S: DatabaseSource> SharedDatabase<S> {
+    /// Create a new shared handle with the provided database as the initial snapshot.
+    pub fn new(source: S) -> Self {
+        Self {
+            current: ArcSwap::from_pointee(source),
+        }
+    }
+
+    /// Get the current database snapshot.
+    ///
+    /// The snapshot stays valid and unchanged even if the database is replaced in the meantime.
+    pub fn snapshot(&self) -> Arc<S> {
+        self.current.load_full()
+    }
+
+    /// Install a new database. Subsequent snapshots will use the new database.
+    ///
+    /// Returns the previous snapshot, which is dropped once every query using it finished.
+    pub fn replace(&self, source: S) -> Arc<S> {
+        self.current.swap(Arc::new(source))
+    }
+}
+
+impl SharedDatabase {
+    /// Create a new type-erased shared handle from any database backend.
+    pub fn from_source<T: DatabaseSource + Send + Sync + 'static>(source: T) -> Self {
+        Self::new(Box::new(source))
+    }
+
+    /// Install a new database of any backend type.
+    pub fn replace_source<T: DatabaseSource + Send + Sync + 'static>(
+        &self,
+        source: T,
+    ) -> Arc<DynDatabaseSource> {
+        self.replace(Box::new(source))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shared::SharedDatabase;
+    use crate::source::DatabaseSource;
+    use crate::Database;
+    use std::sync::Arc;
+
+    #[test]
+    pub fn test_snapshot_survives_replace() {
+        let shared = SharedDatabase::from_source(Database::new(1));
+        let old_snapshot = shared.snapshot();
+
+        let previous = shared.replace_source(Database::new(2));
+        assert_eq!(previous.info().database_version, 1);
+        assert_eq!(old_snapshot.info().database_version, 1);
+        assert_eq!(shared.snapshot().info().database_version, 2);
+    }
+
+    #[test]
+    pub fn test_concurrent_readers() {
+        let shared = Arc::new(SharedDatabase::new(Database::new(1)));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        let version = shared.snapshot().info().database_version;
+                        assert!(version == 1 || version == 2);
+                    }
+                })
+            })
+            .collect();
+        shared.replace(Database::new(2));
+        readers.into_iter().for_each(|r| r.join().unwrap());
+        assert_eq!(shared.snapshot().info().database_version, 2);
+    }
+}
@@ -0,0 +1,162 @@
+//! Common interface for every database backend.
+//!
+//! [`Database`](crate::Database) keeps every object in memory while
+//! [`LazyLoadedDatabase`](crate::LazyLoadedDatabase) reads them from the file on demand. Code
+//! that only queries objects can be written against [`DatabaseSource`] and work with both.
+//!
+//! # Example: Storage-independent detector
+//! ```rust no_run
+//! use simbiota_database::formats::simple_tlsh::SimpleTLSHObject;
+//! use simbiota_database::{DatabaseSource, GetObjectError};
+//!
+//! fn load_hashes<S: DatabaseSource>(source: &S) -> Result<Vec<String>, GetObjectError> {
+//!     let tlsh_list: SimpleTLSHObject = source.get(0x0001)?;
+//!     Ok(tlsh_list.get_hashes())
+//! }
+//!
+//! fn count_hashes<S: DatabaseSource>(source: &S) -> Result<usize, GetObjectError> {
+//!     let objects = source.find_all::<SimpleTLSHObject>()?;
+//!     Ok(objects.iter().map(|(_, o)| o.get_hashes().len()).sum())
+//! }
+//! ```
+
+use crate::database::{FormatError, Object, ObjectFormat};
+use crate::object::ObjectDecodeError;
+use crate::object_map::ObjectMappingInfo;
+use std::borrow::Cow;
+
+/// Error representing failures that can occur while querying a [`DatabaseSource`].
+#[derive(Debug)]
+pub enum SourceError {
+    /// There is no object with the requested ID in the database.
+    NotFound(u64),
+    /// Reading the backing storage failed.
+    IOError(std::io::Error),
+    /// The stored object could not be decoded.
+    InvalidObject(ObjectDecodeError),
+}
+
+/// Error representing failures that can occur while reading an object in a specific format.
+#[derive(Debug)]
+pub enum GetObjectError {
+    /// There is no object with the requested ID in the database.
+    NotFound(u64),
+    /// The object is not in the requested format, or it is invalid.
+    InvalidFormat { id: u64, error: FormatError },
+    /// Querying the source failed.
+    SourceError(SourceError),
+}
+
+impl From<SourceError> for GetObjectError {
+    fn from(value: SourceError) -> Self {
+        match value {
+            SourceError::NotFound(id) => GetObjectError::NotFound(id),
+            e => GetObjectError::SourceError(e),
+        }
+    }
+}
+
+/// General information about a database, read from its header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatabaseInfo {
+    /// Version of the file format.
+    pub file_version: u32,
+    /// Version of the stored dataset.
+    pub database_version: u64,
+    /// Modification time as a unix timestamp. Zero if the database was never saved.
+    pub last_updated: u64,
+    /// Number of objects in the database.
+    pub number_of_objects: u64,
+}
+
+/// Information about a single object, available without decoding its entries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectMetadata {
+    pub format: u16,
+    pub compression: u16,
+    pub entry_type: u16,
+    pub entry_size: u16,
+    /// Stored length of the object (header + entries) without the padding, if known.
+    pub length: Option<u64>,
+    /// Number of entries in the object, if known without decompressing the data.
+    pub entry_count: Option<u64>,
+}
+
+impl From<&ObjectMappingInfo> for ObjectMetadata {
+    fn from(value: &ObjectMappingInfo) -> Self {
+        Self {
+            format: value.format,
+            compression: value.compression,
+            entry_type: value.entry_type,
+            entry_size: value.entry_size,
+            length: Some(value.length),
+            entry_count: Some(value.entry_count),
+        }
+    }
+}
+
+/// Trait implemented by every database backend.
+pub trait DatabaseSource {
+    /// IDs of all the objects in the database.
+    fn object_ids(&self) -> Result<Vec<u64>, SourceError>;
+    /// Check if the database contains a specified object.
+    fn has_object(&self, id: u64) -> Result<bool, SourceError>;
+    /// Get an object by its ID. In-memory backends return a borrowed object, others read and
+    /// decode it on each call.
+    fn get_object(&self, id: u64) -> Result<Cow<'_, Object>, SourceError>;
+    /// General information about the database.
+    fn info(&self) -> DatabaseInfo;
+    /// Object header information of the specified object.
+    fn metadata(&self, id: u64) -> Result<ObjectMetadata, SourceError>;
+
+    /// Get an object by its ID and parse it as `T`.
+    fn get<T: ObjectFormat>(&self, id: u64) -> Result<T, GetObjectError>
+    where
+        Self: Sized,
+    {
+        let object = self.get_object(id)?;
+        T::try_from_object(&object).map_err(|error| GetObjectError::InvalidFormat { id, error })
+    }
+
+    /// Get every object in the format of `T`, ordered by ID.
+    ///
+    /// Objects are selected by the format and entry type in their metadata, only those are read.
+    /// Fails if any of the selected objects is invalid.
+    fn find_all<T: ObjectFormat>(&self) -> Result<Vec<(u64, T)>, GetObjectError>
+    where
+        Self: Sized,
+    {
+        let mut ids = self.object_ids()?;
+        ids.sort_unstable();
+        let mut objects = Vec::new();
+        for id in ids {
+            let metadata = self.metadata(id)?;
+            if metadata.format == T::FORMAT && T::ENTRY_TYPES.contains(&metadata.entry_type) {
+                objects.push((id, self.get(id)?));
+            }
+        }
+        Ok(objects)
+    }
+}
+
+impl<T: DatabaseSource + ?Sized> DatabaseSource for Box<T> {
+    fn object_ids(&self) -> Result<Vec<u64>, SourceError> {
+        (**self).object_ids()
+    }
+
+    fn has_object(&self, id: u64) -> Result<bool, SourceError> {
+        (**self).has_object(id)
+    }
+
+    fn get_object(&self, id: u64) -> Result<Cow<'_, Object>, SourceError> {
+        (**self).get_object(id)
+    }
+
+    fn info(&self) -> DatabaseInfo {
+        (**self).info()
+    }
+
+    fn metadata(&self, id: u64) -> Result<ObjectMetadata, SourceError> {
+        (**self).metadata(id)
+    }
+}
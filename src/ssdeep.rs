@@ -0,0 +1,273 @@
+//! ssdeep digest parsing and comparison.
+//!
+//! [`Ssdeep`] parses the `blocksize:hash1:hash2` digest format of ssdeep and computes the same
+//! 0-100 match score as `fuzzy_compare` of the reference implementation.
+//!
+//! # Example: Compare two digests
+//! ```rust
+//! use simbiota_database::ssdeep::Ssdeep;
+//!
+//! let a = Ssdeep::parse("48:abcdefghij:qrstuvw").unwrap();
+//! let b = Ssdeep::parse("48:abcdefghXY:QRSTUVW").unwrap();
+//! assert_eq!(a.compare(&b), 82);
+//! ```
+
+use crate::ssdeep::SsdeepParseError::{InvalidBlockSize, InvalidCharacter, InvalidFormat, TooLong};
+use std::fmt::{Display, Formatter};
+
+/// Maximum length of the first hash part.
+pub const SPAMSUM_LENGTH: usize = 64;
+/// Maximum length of the second hash part.
+pub const SPAMSUM_HALF_LENGTH: usize = SPAMSUM_LENGTH / 2;
+
+const MIN_BLOCKSIZE: u32 = 3;
+const ROLLING_WINDOW: usize = 7;
+
+#[derive(Debug, PartialEq)]
+pub enum SsdeepParseError {
+    /// The digest is not in the `blocksize:hash1:hash2` format.
+    InvalidFormat,
+    /// The block size is not a number, or not a power of two multiple of the minimum block size.
+    InvalidBlockSize,
+    /// One of the hash parts is longer than the maximum.
+    TooLong,
+    /// One of the hash parts contains a non-base64 character.
+    InvalidCharacter,
+}
+
+/// A parsed ssdeep digest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ssdeep {
+    block_size: u32,
+    part1: String,
+    part2: String,
+}
+
+impl Ssdeep {
+    /// Parse a digest. A trailing `,"filename"` part, as printed by ssdeep, is ignored.
+    pub fn parse(digest: &str) -> Result<Self, SsdeepParseError> {
+        let digest = digest.split(',').next().unwrap_or(digest);
+        let mut parts = digest.splitn(3, ':');
+        let (Some(block_size), Some(part1), Some(part2)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(InvalidFormat);
+        };
+        let block_size: u32 = block_size.parse().map_err(|_| InvalidBlockSize)?;
+        Self::new(block_size, part1, part2)
+    }
+
+    /// Create a digest from its components.
+    pub fn new(block_size: u32, part1: &str, part2: &str) -> Result<Self, SsdeepParseError> {
+        if block_size < MIN_BLOCKSIZE
+            || !block_size.is_multiple_of(MIN_BLOCKSIZE)
+            || !(block_size / MIN_BLOCKSIZE).is_power_of_two()
+        {
+            return Err(InvalidBlockSize);
+        }
+        if part1.len() > SPAMSUM_LENGTH || part2.len() > SPAMSUM_HALF_LENGTH {
+            return Err(TooLong);
+        }
+        let is_base64 = |c: char| c.is_ascii_alphanumeric() || c == '+' || c == '/';
+        if !part1.chars().chain(part2.chars()).all(is_base64) {
+            return Err(InvalidCharacter);
+        }
+        Ok(Self {
+            block_size,
+            part1: part1.to_string(),
+            part2: part2.to_string(),
+        })
+    }
+
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    /// Hash part computed with the block size.
+    pub fn part1(&self) -> &str {
+        &self.part1
+    }
+
+    /// Hash part computed with double the block size.
+    pub fn part2(&self) -> &str {
+        &self.part2
+    }
+
+    /// Match score of two digests, from 0 (no similarity) to 100 (identical).
+    ///
+    /// Digests can only be compared if their block sizes are equal, or one is double the other.
+    pub fn compare(&self, other: &Ssdeep) -> u32 {
+        // The doubled block size can overflow u32 for the largest valid block size
+        let (bs1, bs2) = (self.block_size as u64, other.block_size as u64);
+        if bs1 != bs2 && bs1 * 2 != bs2 && bs2 * 2 != bs1 {
+            return 0;
+        }
+
+        let s1b1 = eliminate_sequences(&self.part1);
+        let s1b2 = eliminate_sequences(&self.part2);
+        let s2b1 = eliminate_sequences(&other.part1);
+        let s2b2 = eliminate_sequences(&other.part2);
+
+        if bs1 == bs2 && s1b1 == s2b1 && s1b2 == s2b2 {
+            return 100;
+        }
+
+        if bs1 == bs2 {
+            score_strings(&s1b1, &s2b1, bs1).max(score_strings(&s1b2, &s2b2, bs1 * 2))
+        } else if bs1 * 2 == bs2 {
+            score_strings(&s1b2, &s2b1, bs2)
+        } else {
+            score_strings(&s1b1, &s2b2, bs1)
+        }
+    }
+}
+
+impl Display for Ssdeep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.block_size, self.part1, self.part2)
+    }
+}
+
+/// Remove the characters repeated more than three times in a row, they carry little information.
+fn eliminate_sequences(part: &str) -> Vec<u8> {
+    let bytes = part.as_bytes();
+    bytes
+        .iter()
+        .enumerate()
+        .filter(|(i, b)| *i < 3 || bytes[i - 3..*i].iter().any(|p| p != *b))
+        .map(|(_, b)| *b)
+        .collect()
+}
+
+/// Score of two hash parts computed with the same block size.
+fn score_strings(s1: &[u8], s2: &[u8], block_size: u64) -> u32 {
+    if s1.len() > SPAMSUM_LENGTH || s2.len() > SPAMSUM_LENGTH || !has_common_substring(s1, s2) {
+        return 0;
+    }
+
+    let score = edit_distance(s1, s2) * SPAMSUM_LENGTH as u32 / (s1.len() + s2.len()) as u32;
+    let score = 100 * score / SPAMSUM_LENGTH as u32;
+    if score >= 100 {
+        return 0;
+    }
+    let score = 100 - score;
+
+    // Small block sizes can produce false matches, limit the score based on the hash length
+    if block_size >= (99 + ROLLING_WINDOW as u64) / ROLLING_WINDOW as u64 * MIN_BLOCKSIZE as u64 {
+        return score;
+    }
+    score.min((block_size / MIN_BLOCKSIZE as u64 * s1.len().min(s2.len()) as u64) as u32)
+}
+
+/// Check if the parts have a common substring of at least the rolling window length.
+fn has_common_substring(s1: &[u8], s2: &[u8]) -> bool {
+    if s1.len() < ROLLING_WINDOW || s2.len() < ROLLING_WINDOW {
+        return false;
+    }
+    s1.windows(ROLLING_WINDOW)
+        .any(|w1| s2.windows(ROLLING_WINDOW).any(|w2| w1 == w2))
+}
+
+/// Edit distance with insertion and deletion costing 1, substitution costing 2.
+fn edit_distance(s1: &[u8], s2: &[u8]) -> u32 {
+    let mut previous: Vec<u32> = (0..=s2.len() as u32).collect();
+    let mut current = vec![0; s2.len() + 1];
+    for (i, c1) in s1.iter().enumerate() {
+        current[0] = i as u32 + 1;
+        for (j, c2) in s2.iter().enumerate() {
+            let substitution = previous[j] + if c1 == c2 { 0 } else { 2 };
+            current[j + 1] = (previous[j + 1] + 1).min(current[j] + 1).min(substitution);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[s2.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ssdeep::{edit_distance, eliminate_sequences, Ssdeep, SsdeepParseError};
+
+    #[test]
+    pub fn test_parse() {
+        let digest = Ssdeep::parse("96:abcdefghij:klmnop,\"sample.bin\"").unwrap();
+        assert_eq!(digest.block_size(), 96);
+        assert_eq!(digest.part1(), "abcdefghij");
+        assert_eq!(digest.part2(), "klmnop");
+        assert_eq!(digest.to_string(), "96:abcdefghij:klmnop");
+
+        assert_eq!(
+            Ssdeep::parse("96:abc"),
+            Err(SsdeepParseError::InvalidFormat)
+        );
+        assert_eq!(
+            Ssdeep::parse("7:abc:def"),
+            Err(SsdeepParseError::InvalidBlockSize)
+        );
+        assert_eq!(
+            Ssdeep::parse("x:abc:def"),
+            Err(SsdeepParseError::InvalidBlockSize)
+        );
+        assert_eq!(
+            Ssdeep::parse(&format!("3:abc:{}", "a".repeat(33))),
+            Err(SsdeepParseError::TooLong)
+        );
+        assert_eq!(
+            Ssdeep::parse("3:ab-c:def"),
+            Err(SsdeepParseError::InvalidCharacter)
+        );
+    }
+
+    #[test]
+    pub fn test_helpers() {
+        assert_eq!(eliminate_sequences("aaaaabcccc"), b"aaabccc");
+        assert_eq!(edit_distance(b"abc", b"abc"), 0);
+        assert_eq!(edit_distance(b"abc", b"abd"), 2);
+        assert_eq!(edit_distance(b"abc", b"ab"), 1);
+        assert_eq!(edit_distance(b"", b"abcd"), 4);
+    }
+
+    #[test]
+    pub fn test_compare() {
+        let a = Ssdeep::parse("48:abcdefghij:qqqqqqq").unwrap();
+        assert_eq!(a.compare(&a), 100);
+
+        // Edit distance 4, 4 * 64 / 20 = 12, 100 * 12 / 64 = 18, score 100 - 18
+        let b = Ssdeep::parse("48:abcdefghXY:abcdefgh").unwrap();
+        assert_eq!(a.compare(&b), 82);
+        assert_eq!(b.compare(&a), 82);
+
+        // Same at block size 3, but limited to 3 / 3 * 10
+        let a3 = Ssdeep::parse("3:abcdefghij:zzzzzzz").unwrap();
+        let b3 = Ssdeep::parse("3:abcdefghXY:yyyyyyy").unwrap();
+        assert_eq!(a3.compare(&b3), 10);
+
+        // Block size 48 and 96: the second part of the first digest is compared to the first part
+        // of the second. Edit distance 1, 1 * 64 / 15 = 4, 100 * 4 / 64 = 6
+        let c = Ssdeep::parse("96:abcdefg:zzzzzzz").unwrap();
+        assert_eq!(b.compare(&c), 94);
+        assert_eq!(c.compare(&b), 94);
+
+        // Incompatible block sizes
+        let d = Ssdeep::parse("192:abcdefghij:qqqqqqq").unwrap();
+        assert_eq!(a.compare(&d), 0);
+
+        // No common substring of 7 characters
+        let e = Ssdeep::parse("48:abcdefXghij:zzzzzzz").unwrap();
+        let f = Ssdeep::parse("48:abcdefYghij:yyyyyyy").unwrap();
+        assert_eq!(e.compare(&f), 0);
+
+        // Long runs are shortened before comparing
+        let g = Ssdeep::parse("48:aaaaaaaabcdefg:x").unwrap();
+        let h = Ssdeep::parse("48:aaabcdefg:x").unwrap();
+        assert_eq!(g.compare(&h), 100);
+
+        // The largest valid block size must not overflow when doubled
+        let max = 3 << 30;
+        let i = Ssdeep::new(max, "abcdefghij", "abcdefgXYZ").unwrap();
+        let j = Ssdeep::new(max, "abcdefghij", "abcdefgXYW").unwrap();
+        assert_eq!(i.compare(&j), 100);
+        let k = Ssdeep::new(max / 2, "zzzzzzz", "abcdefghij").unwrap();
+        assert_eq!(i.compare(&k), 100);
+        assert_eq!(k.compare(&i), 100);
+    }
+}
@@ -0,0 +1,138 @@
+//! telfhash computation for ELF binaries.
+//!
+//! telfhash is a TLSH digest of the sorted, comma separated list of the exported function symbols
+//! of an ELF file. Binaries built from the same code share most of their symbols, so their
+//! telfhash digests are close even if the files themselves differ, e.g. because of packing.
+//!
+//! Only the symbol based digest is supported. Stripped, statically linked binaries without
+//! usable symbols produce [`TelfhashError::NoSymbols`].
+//!
+//! # Example: Compute the telfhash of a file
+//! ```rust no_run
+//! use simbiota_database::telfhash::telfhash;
+//!
+//! let data = std::fs::read("/bin/ls").unwrap();
+//! let digest = telfhash(&data).expect("failed to compute telfhash");
+//! println!("{}", digest.to_hex());
+//! ```
+
+use crate::telfhash::TelfhashError::{InvalidElf, NoSymbols, TooFewSymbols};
+use crate::tlsh::Tlsh;
+use goblin::elf::sym::{STB_GLOBAL, STT_FUNC, STV_DEFAULT};
+use goblin::elf::Elf;
+
+/// Common libc and runtime functions, excluded because they appear in most binaries.
+const EXCLUDED_SYMBOLS: [&str; 8] = [
+    "__libc_start_main",
+    "main",
+    "abort",
+    "cachectl",
+    "cacheflush",
+    "puts",
+    "atol",
+    "malloc_trim",
+];
+
+#[derive(Debug)]
+pub enum TelfhashError {
+    /// The data is not a valid ELF file.
+    InvalidElf(goblin::error::Error),
+    /// The file has no usable function symbols.
+    NoSymbols,
+    /// The symbol list is too short or too uniform to compute a TLSH digest, even a forced one.
+    TooFewSymbols,
+}
+
+/// Compute the telfhash digest of an ELF file.
+///
+/// Like the reference implementation, the digest is forced (see [`Tlsh::force_hash`]), so short
+/// symbol lists also produce a digest.
+pub fn telfhash(elf_data: &[u8]) -> Result<Tlsh, TelfhashError> {
+    let symbols = function_symbols(elf_data)?;
+    if symbols.is_empty() {
+        return Err(NoSymbols);
+    }
+    Tlsh::force_hash(symbols.join(",").as_bytes()).ok_or(TooFewSymbols)
+}
+
+/// The sorted, lowercase names of the symbols used for the telfhash digest.
+///
+/// These are the global, default visibility function symbols of both the static and the dynamic
+/// symbol table, except the names that are common to most binaries.
+pub fn function_symbols(elf_data: &[u8]) -> Result<Vec<String>, TelfhashError> {
+    let elf = Elf::parse(elf_data).map_err(InvalidElf)?;
+
+    let static_symbols = elf.syms.iter().map(|s| (s, &elf.strtab));
+    let dynamic_symbols = elf.dynsyms.iter().map(|s| (s, &elf.dynstrtab));
+    let mut symbols: Vec<String> = static_symbols
+        .chain(dynamic_symbols)
+        .filter(|(sym, _)| {
+            sym.st_type() == STT_FUNC
+                && sym.st_bind() == STB_GLOBAL
+                && sym.st_visibility() == STV_DEFAULT
+        })
+        .filter_map(|(sym, strtab)| strtab.get_at(sym.st_name))
+        .map(|name| name.to_lowercase())
+        .filter(|name| !name.is_empty() && !is_excluded(name))
+        .collect();
+    symbols.sort_unstable();
+    symbols.dedup();
+    Ok(symbols)
+}
+
+fn is_excluded(name: &str) -> bool {
+    name.starts_with('_')
+        || name.starts_with('.')
+        || name.ends_with("64")
+        || name.starts_with("str")
+        || name.starts_with("mem")
+        || EXCLUDED_SYMBOLS.contains(&name)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::telfhash::{function_symbols, is_excluded, telfhash, TelfhashError};
+    use crate::tlsh::Tlsh;
+
+    #[test]
+    pub fn test_exclusions() {
+        assert!(is_excluded("__libc_csu_init"));
+        assert!(is_excluded(".init"));
+        assert!(is_excluded("fopen64"));
+        assert!(is_excluded("strcpy"));
+        assert!(is_excluded("memcpy"));
+        assert!(is_excluded("main"));
+        assert!(!is_excluded("socket"));
+    }
+
+    #[test]
+    pub fn test_invalid_elf() {
+        assert!(matches!(
+            telfhash(b"not an elf file"),
+            Err(TelfhashError::InvalidElf(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_fixture() {
+        // Built from tests/data/telfhash_fixture.c, the names match `readelf --dyn-syms`
+        let data = include_bytes!("../tests/data/telfhash_fixture.so");
+        let symbols = function_symbols(data).unwrap();
+        assert_eq!(
+            symbols,
+            vec![
+                "attack_vector_0",
+                "attack_vector_1",
+                "attack_vector_2",
+                "attack_vector_3",
+                "attack_vector_4",
+            ]
+        );
+
+        // The symbol list is too short for the standard bucket count check
+        let joined = symbols.join(",");
+        assert!(Tlsh::hash(joined.as_bytes()).is_none());
+        let digest = telfhash(data).unwrap();
+        assert_eq!(Some(digest), Tlsh::force_hash(joined.as_bytes()));
+    }
+}
@@ -0,0 +1,531 @@
+//! TLSH digest generation, parsing and distance computation.
+//!
+//! The formats store TLSH digests in the binary form of their hex representation: the checksum,
+//! L-value and Q-ratio bytes are nibble-swapped and the body is stored in reverse order, exactly
+//! as `decode_hex` of the 70 character digest string produces them. [`Tlsh`] decodes this form
+//! and computes the standard TLSH distance score.
+//!
+//! Two binary layouts are supported:
+//! - 35 bytes: the standard TLSH digest (1 byte checksum, 128 buckets), used by SimpleTLSH
+//! - 36 bytes: the "colored" variant used by ColoredTLSH and ColoredTLSHWithDistance. The first 35
+//!   bytes are a standard digest, the last byte is the colour of the digest. The colour is not part
+//!   of the distance score.
+//!
+//! # Example: Compare two digests
+//! ```rust
+//! use simbiota_database::tlsh::Tlsh;
+//!
+//! let a = Tlsh::from_hex("B911A8DACB5B5A06568B6ED299B18014C811DD897E95B720B871B1F5EF7300538187DC").unwrap();
+//! let b = Tlsh::from_hex("T1B911A8DACB5B5A06568B6ED299B18014C811DD897E95B720B871B1F5EF7300538187DC").unwrap();
+//! assert_eq!(a.distance(&b), 0);
+//! ```
+
+use crate::hash::{decode_hex, HashParseError};
+use crate::tlsh::TlshParseError::{InvalidHex, InvalidLength};
+
+/// Length of a standard binary TLSH digest.
+pub const TLSH_LENGTH: usize = 35;
+/// Length of the colored binary TLSH digest.
+pub const COLORED_TLSH_LENGTH: usize = 36;
+/// Length of the TLSH body in bytes (128 buckets, 2 bits each).
+pub const TLSH_BODY_LENGTH: usize = 32;
+
+const RANGE_LVALUE: u32 = 256;
+const RANGE_QRATIO: u32 = 16;
+const LENGTH_MULT: u32 = 12;
+const QRATIO_MULT: u32 = 12;
+
+const SLIDING_WINDOW_SIZE: usize = 5;
+const BUCKETS: usize = 256;
+const EFFECTIVE_BUCKETS: usize = 128;
+/// Inputs shorter than this can not be hashed.
+pub const MIN_DATA_LENGTH: usize = 50;
+
+/// Pearson hash permutation table of the reference implementation.
+#[rustfmt::skip]
+const V_TABLE: [u8; 256] = [
+    1, 87, 49, 12, 176, 178, 102, 166, 121, 193, 6, 84, 249, 230, 44, 163,
+    14, 197, 213, 181, 161, 85, 218, 80, 64, 239, 24, 226, 236, 142, 38, 200,
+    110, 177, 104, 103, 141, 253, 255, 50, 77, 101, 81, 18, 45, 96, 31, 222,
+    25, 107, 190, 70, 86, 237, 240, 34, 72, 242, 20, 214, 244, 227, 149, 235,
+    97, 234, 57, 22, 60, 250, 82, 175, 208, 5, 127, 199, 111, 62, 135, 248,
+    174, 169, 211, 58, 66, 154, 106, 195, 245, 171, 17, 187, 182, 179, 0, 243,
+    132, 56, 148, 75, 128, 133, 158, 100, 130, 126, 91, 13, 153, 246, 216, 219,
+    119, 68, 223, 78, 83, 88, 201, 99, 122, 11, 92, 32, 136, 114, 52, 10,
+    138, 30, 48, 183, 156, 35, 61, 26, 143, 74, 251, 94, 129, 162, 63, 152,
+    170, 7, 115, 167, 241, 206, 3, 150, 55, 59, 151, 220, 90, 53, 23, 131,
+    125, 173, 15, 238, 79, 95, 89, 16, 105, 137, 225, 224, 217, 160, 37, 123,
+    118, 73, 2, 157, 46, 116, 9, 145, 134, 228, 207, 212, 202, 215, 69, 229,
+    27, 188, 67, 124, 168, 252, 42, 4, 29, 108, 21, 247, 19, 205, 39, 203,
+    233, 40, 186, 147, 198, 192, 155, 33, 164, 191, 98, 204, 165, 180, 117, 76,
+    140, 36, 210, 172, 41, 54, 159, 8, 185, 232, 113, 196, 231, 47, 146, 120,
+    51, 65, 28, 144, 254, 221, 93, 189, 194, 139, 112, 43, 71, 109, 184, 209
+];
+
+#[derive(Debug, PartialEq)]
+pub enum TlshParseError {
+    /// The digest is not 35 or 36 bytes (70 or 72 hex characters) long.
+    InvalidLength(usize),
+    /// The digest string contains a non-hexadecimal character.
+    InvalidHex,
+}
+
+/// A parsed TLSH digest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tlsh {
+    checksum: u8,
+    l_value: u8,
+    q1_ratio: u8,
+    q2_ratio: u8,
+    body: [u8; TLSH_BODY_LENGTH],
+    colour: Option<u8>,
+}
+
+impl Tlsh {
+    /// Parse a binary digest, either the standard 35-byte or the colored 36-byte variant.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TlshParseError> {
+        let colour = match bytes.len() {
+            TLSH_LENGTH => None,
+            COLORED_TLSH_LENGTH => Some(bytes[TLSH_LENGTH]),
+            len => return Err(InvalidLength(len)),
+        };
+
+        Ok(Self {
+            checksum: swap_nibbles(bytes[0]),
+            l_value: swap_nibbles(bytes[1]),
+            q1_ratio: bytes[2] >> 4,
+            q2_ratio: bytes[2] & 0x0f,
+            body: bytes[3..TLSH_LENGTH].try_into().unwrap(),
+            colour,
+        })
+    }
+
+    /// Parse a hex digest. The `T1` version prefix is accepted, but not required.
+    pub fn from_hex(hex: &str) -> Result<Self, TlshParseError> {
+        let hex = hex.strip_prefix("T1").unwrap_or(hex);
+        let bytes = decode_hex(hex).map_err(|e| match e {
+            HashParseError::InvalidLength(len) => InvalidLength(len),
+            HashParseError::InvalidHex => InvalidHex,
+        })?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Compute the TLSH digest of the data.
+    ///
+    /// Returns none if the data is shorter than [`MIN_DATA_LENGTH`] or too uniform to produce a
+    /// meaningful digest (at most half of the buckets are non-empty).
+    pub fn hash(data: &[u8]) -> Option<Self> {
+        Self::compute(data, false)
+    }
+
+    /// Compute the TLSH digest of the data without the bucket count check, like `forcehash` of
+    /// the reference implementation. Used by telfhash, whose symbol lists are often short.
+    ///
+    /// Returns none if the data is shorter than [`MIN_DATA_LENGTH`] or the upper quartile of the
+    /// buckets is empty, as the Q-ratios are undefined then.
+    pub fn force_hash(data: &[u8]) -> Option<Self> {
+        Self::compute(data, true)
+    }
+
+    fn compute(data: &[u8], force: bool) -> Option<Self> {
+        if data.len() < MIN_DATA_LENGTH {
+            return None;
+        }
+
+        let mut buckets = [0u32; BUCKETS];
+        let mut checksum = 0u8;
+        for i in SLIDING_WINDOW_SIZE - 1..data.len() {
+            let w = &data[i + 1 - SLIDING_WINDOW_SIZE..=i];
+            let (w0, w1, w2, w3, w4) = (w[4], w[3], w[2], w[1], w[0]);
+            checksum = b_mapping(0, w0, w1, checksum);
+            buckets[b_mapping(2, w0, w1, w2) as usize] += 1;
+            buckets[b_mapping(3, w0, w1, w3) as usize] += 1;
+            buckets[b_mapping(5, w0, w2, w3) as usize] += 1;
+            buckets[b_mapping(7, w0, w2, w4) as usize] += 1;
+            buckets[b_mapping(11, w0, w1, w4) as usize] += 1;
+            buckets[b_mapping(13, w0, w3, w4) as usize] += 1;
+        }
+
+        let mut sorted = buckets[..EFFECTIVE_BUCKETS].to_vec();
+        sorted.sort_unstable();
+        let (q1, q2, q3) = (
+            sorted[EFFECTIVE_BUCKETS / 4 - 1],
+            sorted[EFFECTIVE_BUCKETS / 2 - 1],
+            sorted[EFFECTIVE_BUCKETS - EFFECTIVE_BUCKETS / 4 - 1],
+        );
+        let non_zero = buckets[..EFFECTIVE_BUCKETS]
+            .iter()
+            .filter(|b| **b > 0)
+            .count();
+        if q3 == 0 || (!force && non_zero <= EFFECTIVE_BUCKETS / 2) {
+            return None;
+        }
+
+        // The code is stored in reverse order, the first byte holds the last four buckets
+        let mut body = [0u8; TLSH_BODY_LENGTH];
+        for (i, code) in buckets[..EFFECTIVE_BUCKETS].chunks_exact(4).enumerate() {
+            body[TLSH_BODY_LENGTH - 1 - i] = code.iter().enumerate().fold(0, |h, (j, k)| {
+                let quartile = match *k {
+                    k if k > q3 => 3,
+                    k if k > q2 => 2,
+                    k if k > q1 => 1,
+                    _ => 0,
+                };
+                h | quartile << (j * 2)
+            });
+        }
+
+        Some(Self {
+            checksum,
+            l_value: l_capturing(data.len()),
+            q1_ratio: ((q1 as u64 * 100 / q3 as u64) % 16) as u8,
+            q2_ratio: ((q2 as u64 * 100 / q3 as u64) % 16) as u8,
+            body,
+            colour: None,
+        })
+    }
+
+    /// The standard 35-byte binary digest, in the stored form. The colour is not included.
+    pub fn to_bytes(&self) -> [u8; TLSH_LENGTH] {
+        let mut bytes = [0u8; TLSH_LENGTH];
+        bytes[0] = swap_nibbles(self.checksum);
+        bytes[1] = swap_nibbles(self.l_value);
+        bytes[2] = self.q1_ratio << 4 | self.q2_ratio;
+        bytes[3..].copy_from_slice(&self.body);
+        bytes
+    }
+
+    /// The 70 character uppercase hex digest, without the `T1` prefix.
+    pub fn to_hex(&self) -> String {
+        self.to_bytes()
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect()
+    }
+
+    /// The checksum byte.
+    pub fn checksum(&self) -> u8 {
+        self.checksum
+    }
+
+    /// The L-value, representing the logarithm of the input length.
+    pub fn l_value(&self) -> u8 {
+        self.l_value
+    }
+
+    /// The first quartile ratio.
+    pub fn q1_ratio(&self) -> u8 {
+        self.q1_ratio
+    }
+
+    /// The second quartile ratio.
+    pub fn q2_ratio(&self) -> u8 {
+        self.q2_ratio
+    }
+
+    /// The digest body, in stored order.
+    pub fn body(&self) -> &[u8; TLSH_BODY_LENGTH] {
+        &self.body
+    }
+
+    /// The colour of a colored digest, `None` for standard digests.
+    pub fn colour(&self) -> Option<u8> {
+        self.colour
+    }
+
+    /// Standard TLSH distance, including the length component. Zero means identical digests.
+    pub fn distance(&self, other: &Tlsh) -> u32 {
+        self.total_diff(other, true)
+    }
+
+    /// TLSH distance without the length component, for comparing inputs of different sizes.
+    pub fn distance_no_length(&self, other: &Tlsh) -> u32 {
+        self.total_diff(other, false)
+    }
+
+    fn total_diff(&self, other: &Tlsh, len_diff: bool) -> u32 {
+        let mut diff = 0;
+
+        if len_diff {
+            let l_diff = mod_diff(self.l_value as u32, other.l_value as u32, RANGE_LVALUE);
+            diff += match l_diff {
+                0 | 1 => l_diff,
+                _ => l_diff * LENGTH_MULT,
+            };
+        }
+
+        for (q, other_q) in [
+            (self.q1_ratio, other.q1_ratio),
+            (self.q2_ratio, other.q2_ratio),
+        ] {
+            let q_diff = mod_diff(q as u32, other_q as u32, RANGE_QRATIO);
+            diff += if q_diff <= 1 {
+                q_diff
+            } else {
+                (q_diff - 1) * QRATIO_MULT
+            };
+        }
+
+        if self.checksum != other.checksum {
+            diff += 1;
+        }
+
+        diff + body_distance(&self.body, &other.body)
+    }
+}
+
+/// Distance of two binary digests (35 or 36 bytes each), including the length component.
+pub fn distance(a: &[u8], b: &[u8]) -> Result<u32, TlshParseError> {
+    Ok(Tlsh::from_bytes(a)?.distance(&Tlsh::from_bytes(b)?))
+}
+
+/// Pearson hash of a salt and three bytes.
+fn b_mapping(salt: u8, i: u8, j: u8, k: u8) -> u8 {
+    let mut h = V_TABLE[salt as usize];
+    h = V_TABLE[(h ^ i) as usize];
+    h = V_TABLE[(h ^ j) as usize];
+    V_TABLE[(h ^ k) as usize]
+}
+
+/// Logarithmic length value of the input, with the constants of the reference implementation.
+fn l_capturing(length: usize) -> u8 {
+    const LOG_1_5: f64 = 0.4054651;
+    const LOG_1_3: f64 = 0.26236426;
+    const LOG_1_1: f64 = 0.095310180;
+
+    let log = (length as f64).ln();
+    let value = if length <= 656 {
+        (log / LOG_1_5).floor()
+    } else if length <= 3199 {
+        (log / LOG_1_3 - 8.72777).floor()
+    } else {
+        (log / LOG_1_1 - 62.5472).floor()
+    };
+    (value as i64 & 0xff) as u8
+}
+
+fn swap_nibbles(value: u8) -> u8 {
+    value.rotate_left(4)
+}
+
+/// Circular distance of two values in the `0..range` range.
+fn mod_diff(x: u32, y: u32, range: u32) -> u32 {
+    let (dl, dr) = if y > x {
+        (y - x, x + range - y)
+    } else {
+        (x - y, y + range - x)
+    };
+    dl.min(dr)
+}
+
+/// Sum of the bucket differences. Each byte stores four 2-bit buckets, a difference of 3 is
+/// penalized as 6.
+fn body_distance(a: &[u8; TLSH_BODY_LENGTH], b: &[u8; TLSH_BODY_LENGTH]) -> u32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| {
+            (0..4)
+                .map(|i| {
+                    let bx = (x >> (i * 2)) & 0b11;
+                    let by = (y >> (i * 2)) & 0b11;
+                    match bx.abs_diff(by) {
+                        3 => 6,
+                        d => d as u32,
+                    }
+                })
+                .sum::<u32>()
+        })
+        .sum()
+}
+
+/// Test digest of `N` bytes (35 or 36), zero except for the first body byte. Each bucket
+/// difference of 1 or 2 counts as-is and 3 as 6, so `first_body_byte` 0, 1, 2 and 3 are at a TLSH
+/// distance of 0, 1, 2 and 6 from the all-zero digest, and 0xff is at 24.
+#[cfg(test)]
+pub(crate) fn test_digest<const N: usize>(first_body_byte: u8) -> [u8; N] {
+    let mut digest = [0u8; N];
+    digest[3] = first_body_byte;
+    digest
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tlsh::{distance, l_capturing, Tlsh, TlshParseError};
+
+    const HASH_A: &str = "B911A8DACB5B5A06568B6ED299B18014C811DD897E95B720B871B1F5EF7300538187DC";
+    const HASH_B: &str = "0B22C01977023F8A74C2CA8D7C4D514C426A3CB17C966FA2A0D96D770E7882C417FE5B";
+
+    fn decode(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Digest with the provided header bytes (as stored) and an all-zero body
+    fn digest(checksum: u8, l_value: u8, q_ratios: u8, body: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![checksum, l_value, q_ratios];
+        bytes.extend_from_slice(body);
+        bytes.resize(35, 0);
+        bytes
+    }
+
+    #[test]
+    pub fn test_parse() {
+        let tlsh = Tlsh::from_hex(HASH_A).unwrap();
+        assert_eq!(tlsh.checksum(), 0x9B);
+        assert_eq!(tlsh.l_value(), 0x11);
+        assert_eq!(tlsh.q1_ratio(), 0xA);
+        assert_eq!(tlsh.q2_ratio(), 0x8);
+        assert_eq!(tlsh.body()[0], 0xDA);
+        assert_eq!(tlsh.colour(), None);
+        assert_eq!(Tlsh::from_hex(&format!("T1{}", HASH_A)).unwrap(), tlsh);
+        assert_eq!(Tlsh::from_bytes(&decode(HASH_A)).unwrap(), tlsh);
+
+        let mut colored = decode(HASH_A);
+        colored.push(0x07);
+        let colored = Tlsh::from_bytes(&colored).unwrap();
+        assert_eq!(colored.colour(), Some(0x07));
+        assert_eq!(colored.body(), tlsh.body());
+
+        assert_eq!(
+            Tlsh::from_bytes(&[0; 34]),
+            Err(TlshParseError::InvalidLength(34))
+        );
+        assert_eq!(
+            Tlsh::from_hex(&HASH_A[..68]),
+            Err(TlshParseError::InvalidLength(34))
+        );
+        assert_eq!(
+            Tlsh::from_hex(&HASH_A.replace('B', "G")),
+            Err(TlshParseError::InvalidHex)
+        );
+    }
+
+    #[test]
+    pub fn test_header_distance() {
+        let base = digest(0x00, 0x00, 0x00, &[]);
+        assert_eq!(distance(&base, &base).unwrap(), 0);
+
+        // Checksum differs: +1
+        assert_eq!(distance(&base, &digest(0x10, 0x00, 0x00, &[])).unwrap(), 1);
+
+        // L-value is stored nibble-swapped: 0x10 is 1, 0x20 is 2, 0xf0 is 15, 0xff is 255
+        // and wraps around to a difference of 1
+        assert_eq!(distance(&base, &digest(0x00, 0x10, 0x00, &[])).unwrap(), 1);
+        assert_eq!(distance(&base, &digest(0x00, 0x20, 0x00, &[])).unwrap(), 24);
+        assert_eq!(
+            distance(&base, &digest(0x00, 0xf0, 0x00, &[])).unwrap(),
+            180
+        );
+        assert_eq!(distance(&base, &digest(0x00, 0xff, 0x00, &[])).unwrap(), 1);
+        assert_eq!(
+            Tlsh::from_bytes(&base)
+                .unwrap()
+                .distance_no_length(&Tlsh::from_bytes(&digest(0x00, 0x20, 0x00, &[])).unwrap()),
+            0
+        );
+
+        // Q1 ratio is the high nibble of the stored byte, Q2 the low one. Both wrap at 16.
+        assert_eq!(distance(&base, &digest(0x00, 0x00, 0x10, &[])).unwrap(), 1);
+        assert_eq!(distance(&base, &digest(0x00, 0x00, 0x30, &[])).unwrap(), 24);
+        assert_eq!(distance(&base, &digest(0x00, 0x00, 0x03, &[])).unwrap(), 24);
+        assert_eq!(distance(&base, &digest(0x00, 0x00, 0xf0, &[])).unwrap(), 1);
+        assert_eq!(
+            distance(&base, &digest(0x00, 0x00, 0x88, &[])).unwrap(),
+            168
+        );
+    }
+
+    #[test]
+    pub fn test_body_distance() {
+        let base = digest(0x00, 0x00, 0x00, &[]);
+        // Bucket differences of 1 and 2 count as-is, 3 counts as 6
+        assert_eq!(distance(&base, &digest(0, 0, 0, &[0b01])).unwrap(), 1);
+        assert_eq!(distance(&base, &digest(0, 0, 0, &[0b10])).unwrap(), 2);
+        assert_eq!(distance(&base, &digest(0, 0, 0, &[0b11])).unwrap(), 6);
+        assert_eq!(distance(&base, &digest(0, 0, 0, &[0xff])).unwrap(), 24);
+        assert_eq!(distance(&base, &digest(0, 0, 0, &[0xff; 32])).unwrap(), 768);
+        assert_eq!(
+            distance(&digest(0, 0, 0, &[0b0110]), &digest(0, 0, 0, &[0b1001])).unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    pub fn test_reference_distance() {
+        // Header: checksum 0x9B/0xB0 (+1), L-value 0x11/0x22 (17 * 12), Q1 10/12 (12), Q2 8/0
+        // (7 * 12), body 192
+        let a = Tlsh::from_hex(HASH_A).unwrap();
+        let b = Tlsh::from_hex(HASH_B).unwrap();
+        assert_eq!(a.distance(&b), 1 + 17 * 12 + 12 + 7 * 12 + 192);
+        assert_eq!(a.distance_no_length(&b), 1 + 12 + 7 * 12 + 192);
+        assert_eq!(a.distance(&b), b.distance(&a));
+
+        // The colour of colored digests does not affect the distance
+        let mut colored_a = decode(HASH_A);
+        colored_a.push(1);
+        let mut colored_b = decode(HASH_B);
+        colored_b.push(2);
+        assert_eq!(distance(&colored_a, &colored_b).unwrap(), a.distance(&b));
+        assert_eq!(distance(&colored_a, &decode(HASH_A)).unwrap(), 0);
+    }
+
+    /// Deterministic pseudo-random test data
+    fn random_data(seed: u64, length: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn test_hash() {
+        assert_eq!(l_capturing(50), 9);
+        assert_eq!(l_capturing(656), 15);
+        assert_eq!(l_capturing(1000), 17);
+        assert_eq!(l_capturing(1 << 20), 82);
+
+        assert!(Tlsh::hash(&[0u8; 49]).is_none());
+        // Not enough distinct triplets for half of the buckets
+        assert!(Tlsh::hash(&[0u8; 1000]).is_none());
+
+        let data = random_data(1, 4096);
+        let tlsh = Tlsh::hash(&data).unwrap();
+        assert_eq!(tlsh.l_value(), l_capturing(4096));
+        assert_eq!(Tlsh::hash(&data), Some(tlsh));
+        assert_eq!(Tlsh::from_bytes(&tlsh.to_bytes()), Ok(tlsh));
+        assert_eq!(Tlsh::from_hex(&tlsh.to_hex()), Ok(tlsh));
+
+        // A small change results in a much smaller distance than unrelated data
+        let mut changed = data.clone();
+        changed[100..110].copy_from_slice(&[0; 10]);
+        let changed = Tlsh::hash(&changed).unwrap();
+        let unrelated = Tlsh::hash(&random_data(2, 4096)).unwrap();
+        assert!(tlsh.distance(&changed) < 30, "{}", tlsh.distance(&changed));
+        assert!(
+            tlsh.distance(&unrelated) > 100,
+            "{}",
+            tlsh.distance(&unrelated)
+        );
+    }
+
+    #[test]
+    pub fn test_force_hash() {
+        // A repeated 20 byte pattern fills more than a quarter, but not half of the buckets
+        let data = b"abcdefghijklmnopqrst".repeat(4);
+        assert!(Tlsh::hash(&data).is_none());
+        let forced = Tlsh::force_hash(&data).unwrap();
+        assert_eq!(forced.l_value(), l_capturing(data.len()));
+
+        let data = random_data(3, 1024);
+        assert_eq!(Tlsh::force_hash(&data), Tlsh::hash(&data));
+        assert!(Tlsh::force_hash(&data[..49]).is_none());
+        assert!(Tlsh::force_hash(&[0u8; 1000]).is_none());
+    }
+}
@@ -0,0 +1,13 @@
+/* Source of telfhash_fixture.so, built with:
+ * gcc -shared -fPIC -nostdlib -Os -s -Wl,-z,noseparate-code -Wl,-z,max-page-size=0x10 \
+ *     -Wl,--build-id=none -o telfhash_fixture.so telfhash_fixture.c
+ */
+int counter;
+
+int attack_vector_0(int n) { return counter + n * 1; }
+int attack_vector_1(int n) { return counter + n * 2; }
+int attack_vector_2(int n) { return counter + n * 3; }
+int attack_vector_3(int n) { return counter + n * 4; }
+int attack_vector_4(int n) { return counter + n * 5; }
+int strcpy_local(int n) { return n; }
+int _hidden_helper(void) { return 1; }
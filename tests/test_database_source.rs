@@ -0,0 +1,234 @@
+use simbiota_database::formats::sha256_set::SHA256SetObject;
+use simbiota_database::formats::simple_tlsh::{SimpleTLSHEntryType, SimpleTLSHObject};
+use simbiota_database::hash::Sha256;
+use simbiota_database::{
+    Database, DatabaseParseError, DatabaseSource, FormatError, GetObjectError, LazyLoadedDatabase,
+    ObjectImpl, SourceError,
+};
+
+fn count_hashes<S: DatabaseSource>(source: &S, id: u64) -> Result<usize, SourceError> {
+    let object = source.get_object(id)?;
+    let tlsh_list = SimpleTLSHObject::from_object(object.into_owned()).unwrap();
+    Ok(tlsh_list.get_hashes().len())
+}
+
+fn generate_database() -> Database {
+    let mut tlsh_object = SimpleTLSHObject::new(SimpleTLSHEntryType::HEX);
+    tlsh_object.add_hash(
+        "B911A8DACB5B5A06568B6ED299B18014C811DD897E95B720B871B1F5EF7300538187DC".to_string(),
+    );
+    tlsh_object.add_hash(
+        "0B22C01977023F8A74C2CA8D7C4D514C426A3CB17C966FA2A0D96D770E7882C417FE5B".to_string(),
+    );
+    let mut database = Database::new(42);
+    database.add_object(1, tlsh_object.to_object());
+    database
+}
+
+#[test]
+fn test_in_memory_source() {
+    let database = generate_database();
+    assert_eq!(database.object_ids().unwrap(), vec![1]);
+    assert!(DatabaseSource::has_object(&database, 1).unwrap());
+    assert!(!DatabaseSource::has_object(&database, 2).unwrap());
+    assert_eq!(count_hashes(&database, 1).unwrap(), 2);
+    assert!(matches!(
+        count_hashes(&database, 2).unwrap_err(),
+        SourceError::NotFound(2)
+    ));
+
+    let info = database.info();
+    assert_eq!(info.database_version, 42);
+    assert_eq!(info.number_of_objects, 1);
+
+    let metadata = database.metadata(1).unwrap();
+    assert_eq!(metadata.format, 0x0001);
+    assert_eq!(metadata.entry_size, 70);
+    assert_eq!(metadata.entry_count, Some(2));
+}
+
+#[test]
+fn test_lazy_source() {
+    let path = std::env::temp_dir().join("simbiota_test_lazy_source.sdb");
+    std::fs::write(&path, generate_database().as_bytes()).expect("failed to write file");
+    let database = LazyLoadedDatabase::new(&path).expect("failed to open database");
+
+    assert_eq!(database.object_ids().unwrap(), vec![1]);
+    assert_eq!(count_hashes(&database, 1).unwrap(), 2);
+    assert!(matches!(
+        count_hashes(&database, 2).unwrap_err(),
+        SourceError::NotFound(2)
+    ));
+
+    let info = database.info();
+    assert_eq!(info.file_version, 1);
+    assert_eq!(info.database_version, 42);
+    assert_ne!(info.last_updated, 0);
+
+    let metadata = database.metadata(1).unwrap();
+    assert_eq!(metadata.format, 0x0001);
+    assert_eq!(metadata.length, Some(16 + 2 * 70));
+    assert_eq!(metadata.entry_count, Some(2));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_sorted_object_map() {
+    let mut database = generate_database();
+    for id in [0x20, 0x03, 0x10, 0x7f] {
+        let mut tlsh_object = SimpleTLSHObject::new(SimpleTLSHEntryType::HEX);
+        tlsh_object.add_hash(
+            "3DB633814E9F2046252E5DD0E10FFBC4A54FEB96D02B4A158B33CE97B76888931937B7".to_string(),
+        );
+        database.add_object(id, tlsh_object.to_object());
+    }
+    database.set_file_version(2).unwrap();
+    let path = std::env::temp_dir().join("simbiota_test_sorted_object_map.sdb");
+    std::fs::write(&path, database.as_bytes()).expect("failed to write file");
+
+    let loaded = Database::from_bytes(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(loaded.info().file_version, 2);
+
+    let database = LazyLoadedDatabase::new_minimal(&path).expect("failed to open database");
+    assert_eq!(
+        database.object_ids().unwrap(),
+        vec![0x01, 0x03, 0x10, 0x20, 0x7f]
+    );
+    assert!(database.has_object(0x10));
+    assert!(!database.has_object(0x11));
+    assert!(database.try_has_object(0x10).unwrap());
+    assert!(!database.try_has_object(0x11).unwrap());
+    assert_eq!(count_hashes(&database, 1).unwrap(), 2);
+    assert_eq!(count_hashes(&database, 0x7f).unwrap(), 1);
+    assert!(matches!(
+        count_hashes(&database, 0x80).unwrap_err(),
+        SourceError::NotFound(0x80)
+    ));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_truncated_object_map() {
+    let mut database = generate_database();
+    database.set_file_version(2).unwrap();
+    let data = database.as_bytes();
+    let path = std::env::temp_dir().join("simbiota_test_truncated_object_map.sdb");
+    // Keep the header only, the object map is read on demand
+    let header_len = u32::from_be_bytes(data[16..20].try_into().unwrap()) as usize;
+    std::fs::write(&path, &data[..header_len]).expect("failed to write file");
+
+    let database = LazyLoadedDatabase::new_minimal(&path).expect("failed to open database");
+    assert!(matches!(
+        database.object_ids().unwrap_err(),
+        SourceError::IOError(_)
+    ));
+    assert!(database.try_has_object(1).is_err());
+    assert!(!database.has_object(1));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_oversized_object_map() {
+    let mut data = generate_database().as_bytes();
+    let header_len = u32::from_be_bytes(data[16..20].try_into().unwrap()) as usize;
+    data.truncate(header_len);
+    data[8..16].copy_from_slice(&(1u64 << 34).to_be_bytes());
+    let path = std::env::temp_dir().join("simbiota_test_oversized_object_map.sdb");
+    std::fs::write(&path, &data).expect("failed to write file");
+
+    assert!(matches!(
+        LazyLoadedDatabase::new(&path),
+        Err(DatabaseParseError::InvalidObjectMap(_))
+    ));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_unsupported_file_version() {
+    let mut database = generate_database();
+    assert!(matches!(
+        database.set_file_version(4).unwrap_err(),
+        DatabaseParseError::UnsupportedVersion(4)
+    ));
+    assert_eq!(database.info().file_version, 1);
+}
+
+#[test]
+fn test_minimal_requires_sorted_map() {
+    let path = std::env::temp_dir().join("simbiota_test_minimal_requires_sorted_map.sdb");
+    std::fs::write(&path, generate_database().as_bytes()).expect("failed to write file");
+    assert!(LazyLoadedDatabase::new_minimal(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_table_of_contents() {
+    let mut database = generate_database();
+    database.set_file_version(3).unwrap();
+    let path = std::env::temp_dir().join("simbiota_test_table_of_contents.sdb");
+    std::fs::write(&path, database.as_bytes()).expect("failed to write file");
+
+    let loaded = Database::from_bytes(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(loaded.info().file_version, 3);
+    assert_eq!(count_hashes(&loaded, 1).unwrap(), 2);
+
+    let database = LazyLoadedDatabase::new(&path).expect("failed to open database");
+    let metadata = database.metadata(1).unwrap();
+    assert_eq!(metadata.format, 0x0001);
+    assert_eq!(metadata.entry_size, 70);
+    assert_eq!(metadata.length, Some(16 + 2 * 70));
+    assert_eq!(metadata.entry_count, Some(2));
+    assert_eq!(count_hashes(&database, 1).unwrap(), 2);
+
+    let database = LazyLoadedDatabase::new_minimal(&path).expect("failed to open database");
+    assert_eq!(database.metadata(1).unwrap().entry_count, Some(2));
+    assert_eq!(count_hashes(&database, 1).unwrap(), 2);
+    std::fs::remove_file(&path).unwrap();
+}
+
+fn check_typed_access<S: DatabaseSource>(source: &S) {
+    let tlsh_list: SimpleTLSHObject = source.get(1).unwrap();
+    assert_eq!(tlsh_list.get_hashes().len(), 2);
+    assert!(matches!(
+        source.get::<SimpleTLSHObject>(9),
+        Err(GetObjectError::NotFound(9))
+    ));
+    assert!(matches!(
+        source.get::<SimpleTLSHObject>(2),
+        Err(GetObjectError::InvalidFormat {
+            id: 2,
+            error: FormatError::WrongFormat {
+                expected: 0x0001,
+                found: 0x0005
+            }
+        })
+    ));
+
+    let ids: Vec<u64> = source
+        .find_all::<SimpleTLSHObject>()
+        .unwrap()
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    assert_eq!(ids, vec![1, 3]);
+    let sets = source.find_all::<SHA256SetObject>().unwrap();
+    assert_eq!(sets.len(), 1);
+    assert_eq!(sets[0].0, 2);
+    assert!(sets[0].1.contains(&Sha256::from([7; 32])));
+}
+
+#[test]
+fn test_typed_access() {
+    let mut database = generate_database();
+    database.put(2, SHA256SetObject::from_hashes([[7; 32]]));
+    let mut tlsh_object = SimpleTLSHObject::new(SimpleTLSHEntryType::RAW);
+    tlsh_object.add_raw_hash(&[0; 35]);
+    database.put(3, tlsh_object);
+    check_typed_access(&database);
+
+    let path = std::env::temp_dir().join("simbiota_test_typed_access.sdb");
+    std::fs::write(&path, database.as_bytes()).expect("failed to write file");
+    let database = LazyLoadedDatabase::new(&path).expect("failed to open database");
+    check_typed_access(&database);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,53 @@
+use simbiota_database::formats::colored_tlsh::{ColoredTLSHObject, FamilyLabel};
+use simbiota_database::formats::label_map::{LabelMapObject, ResolvedLabel};
+use simbiota_database::formats::string_table::StringTableObject;
+use simbiota_database::{Database, ObjectFormat, ObjectImpl};
+
+#[test]
+fn test_resolve_match_label() {
+    let mut strings = StringTableObject::empty();
+    let mut labels = LabelMapObject::new(3);
+    let mirai = strings.intern("Mirai.B");
+    let botnet = strings.intern("botnet");
+    let arm = strings.intern("arm");
+    labels.add_label([1; 32], mirai, &[botnet, arm]);
+
+    let mut tlsh_object = ColoredTLSHObject::empty();
+    let label = FamilyLabel {
+        string_table_id: 3,
+        index: mirai,
+    };
+    tlsh_object.add_labelled_entry([0x11; 36], [1; 32], label);
+    tlsh_object.add_entry([0x22; 36], [2; 32]);
+
+    let mut database = Database::new(1);
+    database.add_object(1, tlsh_object.to_object());
+    database.add_object(3, strings.to_object());
+    database.add_object(4, labels.to_object());
+    let database = Database::from_bytes(database.as_bytes().as_slice()).unwrap();
+
+    let tlsh_object = ColoredTLSHObject::try_from_object(database.get_object(1).unwrap()).unwrap();
+    let entries = tlsh_object.get_entries();
+    assert_eq!(
+        database.get_label(4, &entries[0].sha256_hash),
+        Some(ResolvedLabel {
+            family: "Mirai.B".to_string(),
+            tags: vec!["botnet".to_string(), "arm".to_string()],
+        })
+    );
+    assert_eq!(database.get_label(4, &entries[1].sha256_hash), None);
+    // The entry names the string table of its label
+    let label = entries[0].label.unwrap();
+    assert_eq!(label.string_table_id, 3);
+    assert_eq!(
+        database.get_string(label.string_table_id, label.index),
+        Some("Mirai.B")
+    );
+    assert_eq!(entries[1].label, None);
+    assert_eq!(database.get_string(3, arm), Some("arm"));
+    assert_eq!(database.get_string(3, 3), None);
+
+    // The label map is not a string table and vice versa
+    assert_eq!(database.get_string(4, 0), None);
+    assert_eq!(database.get_label(3, &entries[0].sha256_hash), None);
+}
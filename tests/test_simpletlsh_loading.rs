@@ -1,20 +1,8 @@
-use database::database::{Database, Object, ObjectImpl};
-use database::formats::simple_tlsh::{SimpleTLSHEntryType, SimpleTLSHObject};
-use database::raw_database_file::RawDatabaseFile;
-use std::path::{Path, PathBuf};
+use simbiota_database::formats::simple_tlsh::{SimpleTLSHEntryType, SimpleTLSHObject};
+use simbiota_database::{Database, ObjectCompressionType, ObjectFormat, ObjectImpl};
+use std::path::Path;
 
-#[test]
-fn test_simpletlsh_load() {
-    let file_data = std::fs::read(Path::new("test_files/generated1.sdb")).unwrap();
-    let raw_database = RawDatabaseFile::try_from(file_data.as_slice()).unwrap();
-    let raw_object = raw_database.objects.get(&1).unwrap();
-    let object = Object::from(raw_object);
-    let tlsh_object: SimpleTLSHObject = SimpleTLSHObject::from_object(object).unwrap();
-    println!("{:#?}", tlsh_object.get_hashes());
-}
-
-#[test]
-fn test_simpletlsh_saving() {
+fn generate_database() -> Vec<u8> {
     let mut tlsh_object = SimpleTLSHObject::new(SimpleTLSHEntryType::HEX);
     tlsh_object.add_hash(
         "B911A8DACB5B5A06568B6ED299B18014C811DD897E95B720B871B1F5EF7300538187DC".to_string(),
@@ -33,8 +21,59 @@ fn test_simpletlsh_saving() {
     );
     let mut database = Database::new(1);
     database.add_object(1, tlsh_object.to_object());
-    let bytes = database.as_bytes();
+    database.as_bytes()
+}
+
+#[test]
+fn test_simpletlsh_load() {
+    let file_data = generate_database();
+    let database = Database::from_bytes(file_data.as_slice()).unwrap();
+    let object = database.get_object(1).unwrap();
+    let tlsh_object: SimpleTLSHObject = SimpleTLSHObject::try_from_object(object).unwrap();
+    println!("{:#?}", tlsh_object.get_hashes());
+    assert_eq!(tlsh_object.get_hashes().len(), 5);
+}
+
+#[test]
+fn test_simpletlsh_saving() {
+    let bytes = generate_database();
+    std::fs::create_dir_all(Path::new("test_files")).expect("failed to create directory");
     std::fs::write(Path::new("test_files/generated1.sdb"), bytes.clone())
         .expect("failed to write file");
-    let raw_db = RawDatabaseFile::try_from(bytes.as_slice()).expect("generated database invalid");
+    Database::from_bytes(bytes.as_slice()).expect("generated database invalid");
+}
+
+#[test]
+fn test_simpletlsh_raw() {
+    let hashes = [
+        "B911A8DACB5B5A06568B6ED299B18014C811DD897E95B720B871B1F5EF7300538187DC",
+        "0B22C01977023F8A74C2CA8D7C4D514C426A3CB17C966FA2A0D96D770E7882C417FE5B",
+    ];
+    for compressed in [false, true] {
+        let mut tlsh_object = SimpleTLSHObject::new(SimpleTLSHEntryType::RAW);
+        tlsh_object.set_compressed(compressed);
+        hashes
+            .iter()
+            .for_each(|h| tlsh_object.add_hash(h.to_string()));
+        let object = tlsh_object.to_object();
+        // Without the compression feature the object falls back to no compression
+        let expected = if compressed && cfg!(feature = "compression") {
+            ObjectCompressionType::DEFLATE
+        } else {
+            ObjectCompressionType::NoCompression
+        };
+        assert_eq!(object.compression_type(), expected);
+        let mut database = Database::new(1);
+        database.add_object(1, object);
+        let bytes = database.as_bytes();
+
+        let database = Database::from_bytes(bytes.as_slice()).unwrap();
+        let object = database.get_object(1).unwrap();
+        let tlsh_object = SimpleTLSHObject::try_from_object(object).unwrap();
+        assert_eq!(tlsh_object.get_hashes(), hashes);
+        let raw_hashes = tlsh_object.get_raw_hashes();
+        assert_eq!(raw_hashes.len(), 2);
+        assert_eq!(raw_hashes[0].len(), 35);
+        assert_eq!(raw_hashes[0][0], 0xB9);
+    }
 }
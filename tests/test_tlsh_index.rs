@@ -0,0 +1,75 @@
+use simbiota_database::formats::colored_tlsh::ColoredTLSHObject;
+use simbiota_database::formats::tlsh_index::TLSHIndexObject;
+use simbiota_database::hash::Sha256;
+use simbiota_database::search::{IndexLoadError, TlshIndex};
+use simbiota_database::tlsh::Tlsh;
+use simbiota_database::{Database, LazyLoadedDatabase, ObjectImpl};
+
+/// Colored digests derived from a single base digest, so they are close to each other
+fn colored_object(count: u8) -> ColoredTLSHObject {
+    let mut object = ColoredTLSHObject::empty();
+    for i in 0..count {
+        let mut digest = [0x5au8; 36];
+        digest[3 + (i as usize % 32)] ^= i;
+        object.add_entry(digest, [i; 32]);
+    }
+    object
+}
+
+fn generate_database() -> Database {
+    let indexed = colored_object(40).to_object();
+    let index = TLSHIndexObject::new(1, &indexed).unwrap();
+    let mut database = Database::new(1);
+    database.add_object(1, indexed);
+    database.add_object(2, index.to_object());
+    database
+}
+
+#[test]
+fn test_load_prebuilt_index() {
+    let path = std::env::temp_dir().join("simbiota_test_tlsh_index.sdb");
+    std::fs::write(&path, generate_database().as_bytes()).expect("failed to write file");
+    let database = LazyLoadedDatabase::new(&path).expect("failed to open database");
+
+    let loaded = TlshIndex::load(&database, 2).unwrap();
+    let rebuilt = TlshIndex::from_object(&database.get_object(1).unwrap()).unwrap();
+    assert_eq!(loaded.len(), 40);
+
+    let query = Tlsh::from_bytes(&[0x5a; 36]).unwrap();
+    assert_eq!(loaded.within(&query, 20), rebuilt.within(&query, 20));
+    assert_eq!(loaded.top_k(&query, 3), rebuilt.top_k(&query, 3));
+    assert_eq!(
+        loaded.top_k(&query, 1)[0].sha256,
+        Some(Sha256::from([0; 32]))
+    );
+
+    assert!(matches!(
+        TlshIndex::load(&database, 1),
+        Err(IndexLoadError::InvalidIndex(1))
+    ));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_stale_index() {
+    let mut database = generate_database();
+
+    // Same entry count, different content
+    let mut changed = colored_object(39);
+    changed.add_entry([0; 36], [0; 32]);
+    database.add_object(1, changed.to_object());
+    assert!(matches!(
+        TlshIndex::load(&database, 2),
+        Err(IndexLoadError::Stale(1))
+    ));
+
+    // Different entry count
+    database.add_object(1, colored_object(41).to_object());
+    assert!(matches!(
+        TlshIndex::load(&database, 2),
+        Err(IndexLoadError::Stale(1))
+    ));
+
+    database.add_object(1, colored_object(40).to_object());
+    assert!(TlshIndex::load(&database, 2).is_ok());
+}
@@ -0,0 +1,43 @@
+use simbiota_database::formats::yara_rules::YaraRulesObject;
+use simbiota_database::{Database, ObjectCompressionType, ObjectFormat, ObjectImpl};
+
+#[test]
+fn test_yara_rules_database() {
+    let sources = [
+        (
+            "mirai",
+            "rule mirai { strings: $a = \"MIRAI\" condition: $a }",
+        ),
+        (
+            "gafgyt",
+            "rule gafgyt { strings: $a = \"gayfgt\" condition: $a }",
+        ),
+    ];
+    for compressed in [false, true] {
+        let mut rules = YaraRulesObject::empty();
+        rules.set_compressed(compressed);
+        sources
+            .iter()
+            .for_each(|(name, source)| rules.add_rule_set(name, source));
+        let object = rules.to_object();
+        // Without the compression feature the object falls back to no compression
+        let expected = if compressed && cfg!(feature = "compression") {
+            ObjectCompressionType::DEFLATE
+        } else {
+            ObjectCompressionType::NoCompression
+        };
+        assert_eq!(object.compression_type(), expected);
+        let mut database = Database::new(1);
+        database.add_object(5, object);
+        let bytes = database.as_bytes();
+
+        let database = Database::from_bytes(bytes.as_slice()).unwrap();
+        let rules = YaraRulesObject::try_from_object(database.get_object(5).unwrap()).unwrap();
+        let loaded: Vec<(&str, &str)> = rules
+            .iter()
+            .map(|r| (r.name.as_str(), r.source.as_str()))
+            .collect();
+        assert_eq!(loaded, sources);
+        assert!(rules.get("gafgyt").unwrap().source.contains("gayfgt"));
+    }
+}